@@ -2,17 +2,47 @@
 #![no_main]
 
 pub use bcm2837_lpa as pac;
-use embedded_hal::serial::{Read, Write};
+use common_types::batch::DeltaBatch;
+use common_types::command::Command;
+use common_types::descriptor::Descriptor;
+use common_types::heartbeat::{HeartbeatTimer, LinkMonitor, LinkState};
+use common_types::hello::{Compatibility, Hello, KindSet};
+use common_types::message::{Message, MessageKind};
+use common_types::reliable::{Event, Link, Transport};
+use common_types::sample::{SequenceStatus, SequenceTracker};
+use common_types::status::{DeviceStatus, ResetReason};
+use common_types::timesync::ClockSync;
 use gpio::GpioExt;
-use nb::block;
 use serial::Serial;
 
 pub mod gpio;
 pub mod serial;
+pub mod timer;
 
-use core::arch::asm;
 use core::panic::PanicInfo;
 
+/// Microseconds to wait for the Nucleo to acknowledge a command
+const RETRANSMIT_TIMEOUT: u32 = 500_000;
+/// Times a command is sent again before giving up on it
+const MAX_RETRIES: u8 = 5;
+
+/// Microseconds between hellos sent until the Nucleo answers one
+const HELLO_PERIOD: u32 = 1_000_000;
+/// Microseconds between heartbeats sent to the Nucleo
+const HEARTBEAT_PERIOD: u32 = 1_000_000;
+/// Microseconds without hearing from the Nucleo before the link is
+/// considered degraded, then lost
+const LINK_DEGRADED_AFTER: u32 = 2_500_000;
+const LINK_LOST_AFTER: u32 = 5_000_000;
+
+/// Microseconds between clock syncs, the Nucleo's timestamps wrap around
+/// every ~9 seconds and have to be synced at least twice as often
+const TIME_SYNC_PERIOD: u32 = 2_000_000;
+/// Microseconds a sync exchange may take before it's too inaccurate to use
+const MAX_ROUND_TRIP: u32 = 100_000;
+/// Microseconds a reading may take to arrive once taken
+const MAX_LATENCY: i32 = 1_000_000;
+
 mod start {
     use core::arch::global_asm;
     global_asm!(".section .text._start");
@@ -28,26 +58,198 @@ pub extern "C" fn _start() -> ! {
     p20o.set_low();
     let mut p21o = pins.p21.into_output();
     p21o.set_low();
+    // Lit while the Nucleo isn't heard from
+    let mut p16o = pins.p16.into_output();
+    p16o.set_high();
 
     let tx = pins.p14.into_alternate_fn0();
     let rx = pins.p15.into_alternate_fn0();
 
-    let mut uart = Serial::uart0(dp.UART0, (tx, rx));
+    let uart = Serial::uart0(dp.UART0, (tx, rx));
+    // Commands are sent reliably, readings are streamed as they come
+    let mut link: Link<_, 4> = Link::new(uart, RETRANSMIT_TIMEOUT, MAX_RETRIES);
+
+    // The Nucleo numbers each kind of reading separately
+    let mut trackers = [
+        SequenceTracker::new(),
+        SequenceTracker::new(),
+        SequenceTracker::new(),
+    ];
+    // Last health report from the Nucleo
+    let mut nucleo_status = DeviceStatus::new(ResetReason::Unknown);
+    // Which Nucleo is on the other end
+    let mut nucleo: Option<Descriptor> = None;
+
+    // Announce ourselves, the Nucleo answers with its own hello. Until it
+    // arrives nothing but the handshake is accepted.
+    let hello = Hello::new(common_types::firmware_version!());
+    let mut usable = Compatibility::Incompatible.usable();
+    let mut answered = false;
+    let mut last_hello = None;
+
+    let mut heartbeats = HeartbeatTimer::new(HEARTBEAT_PERIOD);
+    let mut monitor = LinkMonitor::new(LINK_DEGRADED_AFTER, LINK_LOST_AFTER);
+    // Maps the Nucleo's timestamps to the system timer
+    let mut clock = ClockSync::new(timer::TICK_HZ, MAX_ROUND_TRIP);
+    let mut last_sync = None;
 
     loop {
-        for _ in 1..3_000_000 {
-            unsafe { asm!("nop") }
+        let now = timer::now();
+        if monitor.update(now) == Some(LinkState::Lost) {
+            p16o.set_high();
+        }
+        // Announced again until the Nucleo answers, a hello sent while it
+        // was still booting is lost
+        if !answered && last_hello.map_or(true, |last: u32| now.wrapping_sub(last) >= HELLO_PERIOD)
+        {
+            last_hello = Some(now);
+            if link.send(hello).is_err() {
+                p20o.set_high();
+            }
+        }
+        if usable.contains(MessageKind::Heartbeat) {
+            if let Some(heartbeat) = heartbeats.poll(now) {
+                if link.send(heartbeat).is_err() {
+                    p20o.set_high();
+                }
+            }
+        }
+        if usable.contains(MessageKind::TimeRequest)
+            && last_sync.map_or(true, |last: u32| now.wrapping_sub(last) >= TIME_SYNC_PERIOD)
+        {
+            last_sync = Some(now);
+            if link.send(clock.request(now)).is_err() {
+                p20o.set_high();
+            }
         }
 
-        match block!(uart.read()) {
-            Ok(b) => block!(uart.write(b)).unwrap(),
-            Err(_) => p20o.set_high(),
+        let message = match link.poll(now) {
+            Ok(Some(Event::Received(message))) => message,
+            Ok(Some(Event::Delivered(_)) | None) => continue,
+            // A command never got acknowledged, or the link failed
+            Ok(Some(Event::Failed(_))) | Err(_) => {
+                p20o.set_high();
+                continue;
+            }
+        };
+
+        if monitor.received(now).is_some() {
+            p16o.set_low();
         }
-        p21o.set_high();
-        for _ in 1..3_000_000 {
-            unsafe { asm!("nop") }
+
+        match message {
+            // Refuse messages the Nucleo's firmware may lay out differently
+            message if !usable.contains(message.kind()) => {}
+            Message::Hello(remote) => {
+                let compatibility = hello.check(&remote);
+                if compatibility == Compatibility::Incompatible {
+                    p20o.set_high();
+                }
+                usable = compatibility.usable();
+                answered = true;
+
+                if usable.contains(MessageKind::Command) {
+                    let mut sent = command(&mut link, usable, Command::StartStreaming, now);
+                    if usable.contains(MessageKind::Descriptor) {
+                        sent &= command(&mut link, usable, Command::RequestDescriptor, now);
+                    }
+                    if !sent {
+                        p20o.set_high();
+                    }
+                }
+            }
+            Message::Response(response) if !response.is_ack() => p20o.set_high(),
+            Message::Sample(sample) => {
+                let tracker = &mut trackers[stream(sample.value.kind())];
+                let in_order = track(tracker, sample.seq, sample.timestamp);
+                if in_order && !stale(&clock, sample.timestamp, now) {
+                    p21o.toggle();
+                } else {
+                    p20o.set_high();
+                }
+            }
+            Message::Batch(batch) | Message::DeltaBatch(DeltaBatch(batch)) => {
+                let tracker = &mut trackers[stream(batch.kind())];
+                let (in_order, last) = batch.samples().fold((true, None), |(ok, _), s| {
+                    (track(tracker, s.seq, s.timestamp) && ok, Some(s.timestamp))
+                });
+                // Batches are sent as soon as their last reading is taken
+                let late = last.map_or(false, |timestamp| stale(&clock, timestamp, now));
+                if in_order && !late {
+                    p21o.toggle();
+                } else {
+                    p20o.set_high();
+                }
+            }
+            Message::Sensor(_) | Message::Stats(_) => p21o.toggle(),
+            // Any problem the Nucleo runs into lights the error LED
+            Message::DeviceStatus(status) => {
+                if status.is_faulty() || status.is_worse_than(&nucleo_status) {
+                    p20o.set_high();
+                }
+                nucleo_status = status;
+            }
+            Message::Descriptor(descriptor) => {
+                // Another board took over, its readings, health and clock
+                // start over, and its clock is synced right away
+                if nucleo.map_or(false, |nucleo| nucleo.unique_id != descriptor.unique_id) {
+                    for tracker in &mut trackers {
+                        *tracker = SequenceTracker::new();
+                    }
+                    nucleo_status = DeviceStatus::new(ResetReason::Unknown);
+                    clock = ClockSync::new(timer::TICK_HZ, MAX_ROUND_TRIP);
+                    last_sync = None;
+                }
+                nucleo = Some(descriptor);
+            }
+            // Slow exchanges are just ignored, the next one may do better
+            Message::TimeReply(reply) => {
+                clock.update(&reply, now);
+            }
+            _ => {}
         }
-        p21o.set_low();
+    }
+}
+
+/// Sends a command, reliably if the Nucleo supports it, returning whether it
+/// went out
+fn command<T: Transport, const WINDOW: usize>(
+    link: &mut Link<T, WINDOW>,
+    usable: KindSet,
+    command: Command,
+    now: u32,
+) -> bool {
+    if usable.contains(MessageKind::Sequenced) {
+        link.send_reliable(command, now).is_ok()
+    } else {
+        link.send(command).is_ok()
+    }
+}
+
+/// Index of the sequence of readings of the given kind
+fn stream(kind: MessageKind) -> usize {
+    match kind {
+        MessageKind::Acceleration => 1,
+        MessageKind::AngularRate => 2,
+        _ => 0,
+    }
+}
+
+/// Records a received sample, returning whether it arrived in order
+fn track(tracker: &mut SequenceTracker, seq: u16, timestamp: u32) -> bool {
+    match tracker.update(seq, timestamp) {
+        SequenceStatus::First | SequenceStatus::InOrder => true,
+        SequenceStatus::Gap { .. } | SequenceStatus::Duplicate => false,
+    }
+}
+
+/// Whether a reading with the Nucleo's `timestamp` took too long to arrive
+/// by `now`, or is dated after it arrived, which means the clocks aren't in
+/// sync. Unknown until the clocks are synced.
+fn stale(clock: &ClockSync, timestamp: u32, now: u32) -> bool {
+    match clock.to_local(timestamp) {
+        Some(taken) => !(0..=MAX_LATENCY).contains(&(now.wrapping_sub(taken) as i32)),
+        None => false,
     }
 }
 
@@ -1,3 +1,4 @@
+use common_types::reliable::Transport;
 use embedded_hal as hal;
 use hal::serial::{Read, Write};
 use nb::{self, block};
@@ -213,3 +214,23 @@ impl<PINS> Write<u8> for Serial<UART0, PINS> {
         }
     }
 }
+
+impl<PINS> Transport for Serial<UART0, PINS> {
+    type Error = Error;
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        for &byte in bytes {
+            block!(self.write(byte))?;
+        }
+
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+        match self.read() {
+            Ok(byte) => Ok(Some(byte)),
+            Err(nb::Error::WouldBlock) => Ok(None),
+            Err(nb::Error::Other(e)) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! Free running system timer
+
+/// Lower 32 bits of the system timer counter
+// NOTE the peripheral base address is 0x3F000000 on the BCM2837
+const CLO: *const u32 = 0x3F00_3004 as *const u32;
+
+/// Frequency the counter runs at
+pub const TICK_HZ: u32 = 1_000_000;
+
+/// Microseconds since boot, wrapping around every ~71 minutes
+pub fn now() -> u32 {
+    // NOTE(unsafe) Reading the counter has no side effects
+    unsafe { core::ptr::read_volatile(CLO) }
+}
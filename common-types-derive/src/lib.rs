@@ -0,0 +1,249 @@
+//! `#[derive(WireMessage)]` for the `common-types` crate
+//!
+//! Use it through `common_types::WireMessage`, the generated code refers to
+//! items of `common-types`.
+//!
+//! - Structs are encoded as their fields, in declaration order.
+//! - Enums are encoded as a `u8` tag followed by the variant's fields. The
+//!   tag is the variant's discriminant, which must be an integer literal if
+//!   given explicitly.
+//!
+//! Every field is encoded with its own `WireMessage` implementation, which
+//! exists for the primitive types, so everything ends up little endian. All
+//! fields except the last one must always take exactly their `SIZE`, the last
+//! one gets whatever bytes are left.
+//!
+//! With `#[wire(validate)]` decoded values are checked with their `Validate`
+//! implementation before being returned.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DataEnum, DeriveInput, Error, Expr, ExprLit,
+    Fields, Ident, Lit, Result,
+};
+
+#[proc_macro_derive(WireMessage, attributes(wire))]
+pub fn derive_wire_message(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(mut input: DeriveInput) -> Result<TokenStream2> {
+    let validate = parse_attrs(&input.attrs)?;
+
+    let (size, encode, decode) = match &input.data {
+        Data::Struct(data) => expand_struct(&data.fields),
+        Data::Enum(data) => expand_enum(data)?,
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "WireMessage can't be derived for unions",
+            ))
+        }
+    };
+
+    let check = validate.then(|| {
+        quote! { ::common_types::validate::Validate::validate(&value)?; }
+    });
+
+    for param in input.generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::common_types::wire::WireMessage));
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::common_types::wire::WireMessage for #name #ty_generics
+            #where_clause
+        {
+            const SIZE: usize = #size;
+
+            fn encode_into(
+                &self,
+                buf: &mut [u8],
+            ) -> ::core::result::Result<usize, ::common_types::frame::Error> {
+                let mut offset = 0;
+                #encode
+                ::core::result::Result::Ok(offset)
+            }
+
+            fn decode(
+                bytes: &[u8],
+            ) -> ::core::result::Result<Self, ::common_types::message::Error> {
+                let mut offset = 0;
+                let value = #decode;
+                #check
+                ::core::result::Result::Ok(value)
+            }
+        }
+    })
+}
+
+/// Parses the `#[wire(...)]` attributes, returning whether values are
+/// validated
+fn parse_attrs(attrs: &[Attribute]) -> Result<bool> {
+    let mut validate = false;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("wire")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("validate") {
+                validate = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown wire attribute"))
+            }
+        })?;
+    }
+    Ok(validate)
+}
+
+/// Names the fields are bound to while encoding and decoding
+fn bindings(fields: &Fields) -> Vec<Ident> {
+    (0..fields.len())
+        .map(|i| format_ident!("__field{}", i))
+        .collect()
+}
+
+/// Sum of the sizes of `fields`
+fn fields_size(fields: &Fields) -> TokenStream2 {
+    let types = fields.iter().map(|field| &field.ty);
+    quote! { 0 #(+ <#types as ::common_types::wire::WireMessage>::SIZE)* }
+}
+
+/// Encodes the values bound to `names`
+fn encode_fields(names: &[Ident]) -> TokenStream2 {
+    quote! {
+        #(::common_types::wire::encode_field(#names, buf, &mut offset)?;)*
+    }
+}
+
+/// Decodes the values of `fields` and builds `path` out of them
+fn decode_fields(path: TokenStream2, fields: &Fields) -> TokenStream2 {
+    let names = bindings(fields);
+    let Some((last, names)) = names.split_last() else {
+        return quote! {{
+            ::common_types::wire::decode_end(bytes, offset)?;
+            #path
+        }};
+    };
+
+    let construct = match fields {
+        Fields::Named(fields) => {
+            let idents = fields.named.iter().map(|field| &field.ident);
+            let names = names.iter().chain([last]);
+            quote! { #path { #(#idents: #names),* } }
+        }
+        _ => quote! { #path(#(#names,)* #last) },
+    };
+
+    quote! {{
+        #(let #names = ::common_types::wire::decode_field(bytes, &mut offset)?;)*
+        let #last = ::common_types::wire::decode_last_field(bytes, &mut offset)?;
+        #construct
+    }}
+}
+
+/// Pattern binding every field of a struct or variant to [`bindings`]
+fn pattern(path: TokenStream2, fields: &Fields) -> TokenStream2 {
+    let names = bindings(fields);
+    match fields {
+        Fields::Named(fields) => {
+            let idents = fields.named.iter().map(|field| &field.ident);
+            quote! { #path { #(#idents: #names),* } }
+        }
+        Fields::Unnamed(_) => quote! { #path(#(#names),*) },
+        Fields::Unit => path,
+    }
+}
+
+fn expand_struct(fields: &Fields) -> (TokenStream2, TokenStream2, TokenStream2) {
+    let size = fields_size(fields);
+    let pattern = pattern(quote!(Self), fields);
+    let encode_fields = encode_fields(&bindings(fields));
+    let encode = quote! {
+        let #pattern = self;
+        #encode_fields
+    };
+    let decode = decode_fields(quote!(Self), fields);
+    (size, encode, decode)
+}
+
+fn expand_enum(data: &DataEnum) -> Result<(TokenStream2, TokenStream2, TokenStream2)> {
+    let tags = tags(data)?;
+
+    let sizes = data
+        .variants
+        .iter()
+        .map(|variant| fields_size(&variant.fields));
+    let size = quote! {
+        1 + ::common_types::wire::max_size(&[#(#sizes),*])
+    };
+
+    let encode_arms = data.variants.iter().zip(&tags).map(|(variant, tag)| {
+        let ident = &variant.ident;
+        let pattern = pattern(quote!(Self::#ident), &variant.fields);
+        let encode_fields = encode_fields(&bindings(&variant.fields));
+        quote! {
+            #pattern => {
+                ::common_types::wire::encode_field(&#tag, buf, &mut offset)?;
+                #encode_fields
+            }
+        }
+    });
+    let encode = quote! {
+        match self {
+            #(#encode_arms)*
+        }
+    };
+
+    let decode_arms = data.variants.iter().zip(&tags).map(|(variant, tag)| {
+        let ident = &variant.ident;
+        let decode = decode_fields(quote!(Self::#ident), &variant.fields);
+        quote! { #tag => #decode, }
+    });
+    let decode = quote! {
+        match ::common_types::wire::decode_field::<u8>(bytes, &mut offset)? {
+            #(#decode_arms)*
+            _ => return ::core::result::Result::Err(::common_types::message::Error::Invalid(
+                ::common_types::validate::ValidationError::UnknownVariant,
+            )),
+        }
+    };
+
+    Ok((size, encode, decode))
+}
+
+/// Tag of each variant, following the rules for discriminants
+fn tags(data: &DataEnum) -> Result<Vec<u8>> {
+    let mut tags = Vec::with_capacity(data.variants.len());
+    let mut next = Some(0u8);
+
+    for variant in &data.variants {
+        let tag = match &variant.discriminant {
+            Some((
+                _,
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(lit), ..
+                }),
+            )) => lit.base10_parse::<u8>()?,
+            Some((_, expr)) => {
+                return Err(Error::new_spanned(
+                    expr,
+                    "discriminant must be an integer literal",
+                ))
+            }
+            None => {
+                next.ok_or_else(|| Error::new_spanned(&variant.ident, "tag doesn't fit in a u8"))?
+            }
+        };
+        tags.push(tag);
+        next = tag.checked_add(1);
+    }
+
+    Ok(tags)
+}
@@ -0,0 +1,96 @@
+//! Frames authenticated with a pre-shared key
+//!
+//! The CRC only catches bytes mangled on the wire, anyone with access to it
+//! can send a frame with a valid CRC. With a [`Key`] shared by both ends,
+//! every frame is followed by a tag, the first [`TAG_SIZE`] bytes of the
+//! HMAC-SHA256 of everything after the sync marker:
+//!
+//! ```text
+//! +------+------+------+-----+-------------+------------+---------------+
+//! | 0xAA | 0x55 | kind | len | payload ... | crc16 (LE) | tag (8 bytes) |
+//! +------+------+------+-----+-------------+------------+---------------+
+//! ```
+//!
+//! A receiver holding the same key drops frames whose tag doesn't match, see
+//! [`Decoder::with_key`](crate::frame::Decoder::with_key). A recorded frame
+//! still carries a valid tag when it's sent again, so frames carrying a
+//! [`Sequenced`](crate::reliable::Sequenced) message can also be tagged for
+//! a nonce the receiver picks, appended to the tagged bytes as a little
+//! endian `u32`. Once the receiver picks another nonce every frame recorded
+//! until then is rejected, see
+//! [`Link::with_key`](crate::reliable::Link::with_key).
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::frame;
+use crate::message::Message;
+
+/// Bytes of the HMAC kept in every frame
+pub const TAG_SIZE: usize = 8;
+
+/// Size of an authenticated frame carrying a payload of
+/// [`MAX_PAYLOAD`](frame::MAX_PAYLOAD) bytes
+pub const MAX_FRAME_SIZE: usize = frame::MAX_FRAME_SIZE + TAG_SIZE;
+
+/// Pre-shared key, ready to tag frames
+#[derive(Clone)]
+pub struct Key(Hmac<Sha256>);
+
+impl Key {
+    /// Keys of any length work, those longer than 64 bytes are hashed first
+    pub fn new(key: &[u8]) -> Self {
+        Key(Hmac::new_from_slice(key).expect("HMAC takes keys of any length"))
+    }
+
+    /// Truncated HMAC of `data`
+    pub fn tag(&self, data: &[u8]) -> [u8; TAG_SIZE] {
+        self.tag_with_nonce(data, None)
+    }
+
+    /// Whether `tag` is the truncated HMAC of `data`, in constant time
+    pub fn verify(&self, data: &[u8], tag: &[u8]) -> bool {
+        self.verify_with_nonce(data, None, tag)
+    }
+
+    /// Writes `message` into `buf` as an authenticated frame, returning the
+    /// number of bytes written
+    pub fn encode(&self, message: &Message, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        self.encode_with_nonce(message, None, buf)
+    }
+
+    /// Truncated HMAC of `data` followed by `nonce`, if any
+    pub(crate) fn tag_with_nonce(&self, data: &[u8], nonce: Option<u32>) -> [u8; TAG_SIZE] {
+        let mut tag = [0; TAG_SIZE];
+        tag.copy_from_slice(&self.mac(data, nonce).finalize().into_bytes()[..TAG_SIZE]);
+        tag
+    }
+
+    pub(crate) fn verify_with_nonce(&self, data: &[u8], nonce: Option<u32>, tag: &[u8]) -> bool {
+        tag.len() == TAG_SIZE && self.mac(data, nonce).verify_truncated_left(tag).is_ok()
+    }
+
+    /// Like [`encode`](Key::encode), with the tag also covering `nonce`
+    pub(crate) fn encode_with_nonce(
+        &self,
+        message: &Message,
+        nonce: Option<u32>,
+        buf: &mut [u8],
+    ) -> Result<usize, frame::Error> {
+        let len = message.to_frame(buf)?;
+        let tag = self.tag_with_nonce(&buf[frame::SYNC.len()..len], nonce);
+        buf.get_mut(len..len + TAG_SIZE)
+            .ok_or(frame::Error::BufferTooSmall)?
+            .copy_from_slice(&tag);
+        Ok(len + TAG_SIZE)
+    }
+
+    fn mac(&self, data: &[u8], nonce: Option<u32>) -> Hmac<Sha256> {
+        let mut mac = self.0.clone();
+        mac.update(data);
+        if let Some(nonce) = nonce {
+            mac.update(&nonce.to_le_bytes());
+        }
+        mac
+    }
+}
@@ -0,0 +1,292 @@
+//! Several readings of the same kind in a single message
+//!
+//! Sending every reading in its own frame repeats the frame header, the CRC
+//! and the sample header each time, which at 9600 baud leaves little room for
+//! the readings themselves. A [`Batch`] carries consecutive readings of one
+//! kind, taken at a fixed period, behind a single header.
+
+use crate::frame::{self, MAX_PAYLOAD};
+use crate::message::{self, MessageKind};
+use crate::sample::Sample;
+use crate::sensor::SensorMessage;
+use crate::validate::{Validate, ValidationError};
+use crate::wire::WireMessage;
+
+mod delta;
+pub use delta::DeltaBatch;
+
+/// Sequence number, timestamp, period and kind tag
+pub const HEADER_SIZE: usize = 2 + 4 + 4 + 1;
+
+/// Room left for the readings' payloads
+pub const DATA_SIZE: usize = MAX_PAYLOAD - HEADER_SIZE;
+
+/// Error adding a reading to a batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Reading isn't of the batch's kind
+    KindMismatch,
+    /// Batch can't hold another reading
+    Full,
+    /// Reading holds a value the message doesn't allow
+    Invalid(ValidationError),
+}
+
+/// Consecutive readings of a single kind
+///
+/// The payload is the sequence number of the first reading (`u16`), its
+/// timestamp (`u32`), the device ticks between readings (`u32`), the
+/// [`MessageKind`] tag of the readings and their payloads back to back. All
+/// values are little endian. Readings are numbered consecutively from `seq`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Batch {
+    /// Sequence number of the first reading
+    pub seq: u16,
+    /// Device tick count when the first reading was taken
+    pub timestamp: u32,
+    /// Device ticks between consecutive readings
+    pub period: u32,
+    kind: MessageKind,
+    /// Bytes of `data` in use, always a whole number of readings
+    len: usize,
+    data: [u8; DATA_SIZE],
+}
+
+impl Batch {
+    /// Starts an empty batch of readings of the given kind, `None` if the kind
+    /// isn't a reading
+    pub fn new(kind: MessageKind, timestamp: u32) -> Option<Self> {
+        SensorMessage::payload_size(kind)?;
+        Some(Batch {
+            seq: 0,
+            timestamp,
+            period: 0,
+            kind,
+            len: 0,
+            data: [0; DATA_SIZE],
+        })
+    }
+
+    pub fn kind(&self) -> MessageKind {
+        self.kind
+    }
+
+    /// Number of readings in the batch
+    pub fn len(&self) -> usize {
+        self.len / self.reading_size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of readings the batch can hold
+    pub fn capacity(&self) -> usize {
+        DATA_SIZE / self.reading_size()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Adds a reading after the last one
+    pub fn push(&mut self, reading: SensorMessage) -> Result<(), Error> {
+        if reading.kind() != self.kind {
+            return Err(Error::KindMismatch);
+        }
+        if self.is_full() {
+            return Err(Error::Full);
+        }
+        reading.validate().map_err(Error::Invalid)?;
+
+        let n = reading
+            .encode_payload(&mut self.data[self.len..])
+            .expect("Room for a reading was checked");
+        self.len += n;
+        Ok(())
+    }
+
+    /// Readings in the batch, in order
+    pub fn readings(&self) -> impl Iterator<Item = SensorMessage> + '_ {
+        self.data[..self.len]
+            .chunks_exact(self.reading_size())
+            .map(|payload| {
+                SensorMessage::from_payload(self.kind, payload)
+                    .expect("Batch only holds valid readings")
+            })
+    }
+
+    /// Readings in the batch stamped with their own sequence number and
+    /// timestamp
+    pub fn samples(&self) -> impl Iterator<Item = Sample<SensorMessage>> + '_ {
+        self.readings().enumerate().map(|(i, value)| Sample {
+            seq: self.seq.wrapping_add(i as u16),
+            timestamp: self
+                .timestamp
+                .wrapping_add(self.period.wrapping_mul(i as u32)),
+            value,
+        })
+    }
+
+    fn reading_size(&self) -> usize {
+        SensorMessage::payload_size(self.kind).expect("Batch kind is a reading")
+    }
+
+    /// Writes the header into the first [`HEADER_SIZE`] bytes of `buf`
+    fn encode_header(&self, buf: &mut [u8]) {
+        buf[0..2].copy_from_slice(&self.seq.to_le_bytes());
+        buf[2..6].copy_from_slice(&self.timestamp.to_le_bytes());
+        buf[6..10].copy_from_slice(&self.period.to_le_bytes());
+        buf[10] = self.kind.tag();
+    }
+
+    /// Parses the header into an empty batch, returning it with the bytes
+    /// following the header
+    fn decode_header(bytes: &[u8]) -> Result<(Self, &[u8]), message::Error> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(message::Error::InvalidLength);
+        }
+        let u32_at =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let kind = MessageKind::try_from(bytes[10]).map_err(message::Error::UnknownKind)?;
+        let mut batch =
+            Batch::new(kind, u32_at(2)).ok_or(message::Error::UnknownKind(kind.tag()))?;
+        batch.seq = u16::from_le_bytes([bytes[0], bytes[1]]);
+        batch.period = u32_at(6);
+        Ok((batch, &bytes[HEADER_SIZE..]))
+    }
+
+    /// Replaces the readings with the payloads in `data`, checking each one
+    fn set_data(&mut self, data: &[u8]) -> Result<(), message::Error> {
+        if data.len() > DATA_SIZE || !data.len().is_multiple_of(self.reading_size()) {
+            return Err(message::Error::InvalidLength);
+        }
+        for payload in data.chunks_exact(self.reading_size()) {
+            SensorMessage::from_payload(self.kind, payload)?;
+        }
+        self.data[..data.len()].copy_from_slice(data);
+        self.len = data.len();
+        Ok(())
+    }
+}
+
+impl WireMessage for Batch {
+    const SIZE: usize = HEADER_SIZE + DATA_SIZE;
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        let size = HEADER_SIZE + self.len;
+        let buf = buf.get_mut(..size).ok_or(frame::Error::BufferTooSmall)?;
+        self.encode_header(buf);
+        buf[HEADER_SIZE..].copy_from_slice(&self.data[..self.len]);
+        Ok(size)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        let (mut batch, data) = Batch::decode_header(bytes)?;
+        batch.set_data(data)?;
+        Ok(batch)
+    }
+}
+
+/// Serialized as its header fields followed by a sequence of readings
+#[cfg(feature = "serde")]
+impl serde::Serialize for Batch {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        struct Readings<'a>(&'a Batch);
+
+        impl serde::Serialize for Readings<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.readings())
+            }
+        }
+
+        let mut state = serializer.serialize_struct("Batch", 5)?;
+        state.serialize_field("seq", &self.seq)?;
+        state.serialize_field("timestamp", &self.timestamp)?;
+        state.serialize_field("period", &self.period)?;
+        state.serialize_field("kind", &self.kind)?;
+        state.serialize_field("readings", &Readings(self))?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Batch {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use core::fmt;
+        use serde::de::{self, SeqAccess, Visitor};
+
+        /// Readings pushed into a batch as they're deserialized
+        struct Readings(Option<Batch>);
+
+        impl<'de> serde::Deserialize<'de> for Readings {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct ReadingsVisitor;
+
+                impl<'de> Visitor<'de> for ReadingsVisitor {
+                    type Value = Readings;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str("a sequence of readings of the same kind")
+                    }
+
+                    fn visit_seq<A: SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> Result<Readings, A::Error> {
+                        let mut batch: Option<Batch> = None;
+                        while let Some(reading) = seq.next_element::<SensorMessage>()? {
+                            let batch = batch.get_or_insert_with(|| {
+                                Batch::new(reading.kind(), 0).expect("Readings have a batch kind")
+                            });
+                            batch
+                                .push(reading)
+                                .map_err(|e| de::Error::custom(BatchError(e)))?;
+                        }
+                        Ok(Readings(batch))
+                    }
+                }
+
+                deserializer.deserialize_seq(ReadingsVisitor)
+            }
+        }
+
+        struct BatchError(Error);
+
+        impl fmt::Display for BatchError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self.0 {
+                    Error::KindMismatch => f.write_str("readings of different kinds"),
+                    Error::Full => f.write_str("too many readings"),
+                    Error::Invalid(_) => f.write_str("invalid reading"),
+                }
+            }
+        }
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "Batch")]
+        struct Raw {
+            seq: u16,
+            timestamp: u32,
+            period: u32,
+            kind: MessageKind,
+            readings: Readings,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        let mut batch = match raw.readings.0 {
+            Some(batch) if batch.kind != raw.kind => {
+                return Err(de::Error::custom(BatchError(Error::KindMismatch)))
+            }
+            Some(batch) => batch,
+            None => Batch::new(raw.kind, 0)
+                .ok_or_else(|| de::Error::custom("batch kind isn't a reading"))?,
+        };
+        batch.seq = raw.seq;
+        batch.timestamp = raw.timestamp;
+        batch.period = raw.period;
+        Ok(batch)
+    }
+}
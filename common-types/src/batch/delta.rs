@@ -0,0 +1,151 @@
+use super::*;
+use crate::message::{write_payload, Message};
+
+/// Largest LEB128 encoding of a `u32`
+const MAX_VARINT_SIZE: usize = 5;
+
+/// A [`Batch`] sent as the differences between consecutive readings
+///
+/// The payload starts with the same header as a batch. Each reading is split
+/// into values, the `i16` of a fixed point temperature or the bit pattern of
+/// each `f32` otherwise, and every value is sent as its difference to the
+/// same value in the previous reading, the first reading being compared to
+/// zero. Differences are zigzag encoded, so that small negative ones stay
+/// small, and written as LEB128 varints. Only integer arithmetic is involved,
+/// so decoding gives back the exact same readings.
+///
+/// Readings that jump around can take more room than in a plain batch,
+/// [`Batch::compressed`] picks whichever encoding is smaller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeltaBatch(pub Batch);
+
+impl Batch {
+    /// Wraps the batch in a message, delta encoded if that makes it smaller
+    pub fn compressed(self) -> Message {
+        let delta = DeltaBatch(self);
+        match delta.encode_into(&mut [0; MAX_PAYLOAD]) {
+            Ok(len) if len < HEADER_SIZE + self.len => Message::DeltaBatch(delta),
+            _ => Message::Batch(self),
+        }
+    }
+}
+
+impl WireMessage for DeltaBatch {
+    const SIZE: usize = MAX_PAYLOAD;
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        let batch = &self.0;
+        let lane_size = lane_size(batch.reading_size());
+        let lanes = batch.reading_size() / lane_size;
+
+        let mut payload = [0; MAX_PAYLOAD];
+        batch.encode_header(&mut payload);
+        let mut len = HEADER_SIZE;
+
+        let mut previous = [0i32; 3];
+        let values = batch.data[..batch.len].chunks_exact(lane_size);
+        for (i, bytes) in values.enumerate() {
+            let value = read_lane(bytes);
+            let delta = value.wrapping_sub(previous[i % lanes]);
+            previous[i % lanes] = value;
+            write_varint(zigzag(delta), &mut payload, &mut len)?;
+        }
+
+        write_payload(buf, &payload[..len])
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        let (mut batch, mut encoded) = Batch::decode_header(bytes)?;
+        let lane_size = lane_size(batch.reading_size());
+        let lanes = batch.reading_size() / lane_size;
+
+        let mut data = [0; DATA_SIZE];
+        let mut len = 0;
+        let mut previous = [0i32; 3];
+        let mut i = 0;
+        while !encoded.is_empty() {
+            let delta = unzigzag(read_varint(&mut encoded)?);
+            let value = previous[i % lanes].wrapping_add(delta);
+            previous[i % lanes] = value;
+
+            let bytes = data
+                .get_mut(len..len + lane_size)
+                .ok_or(message::Error::InvalidLength)?;
+            write_lane(value, bytes)?;
+            len += lane_size;
+            i += 1;
+        }
+
+        batch.set_data(&data[..len])?;
+        Ok(DeltaBatch(batch))
+    }
+}
+
+impl From<Batch> for DeltaBatch {
+    fn from(batch: Batch) -> Self {
+        DeltaBatch(batch)
+    }
+}
+
+/// Size of each value a reading is split into
+fn lane_size(reading_size: usize) -> usize {
+    reading_size.min(4)
+}
+
+fn read_lane(bytes: &[u8]) -> i32 {
+    match *bytes {
+        [a, b] => i16::from_le_bytes([a, b]) as i32,
+        [a, b, c, d] => i32::from_le_bytes([a, b, c, d]),
+        _ => unreachable!("Values are 2 or 4 bytes long"),
+    }
+}
+
+fn write_lane(value: i32, bytes: &mut [u8]) -> Result<(), message::Error> {
+    if let [_, _] = bytes {
+        let value = i16::try_from(value).map_err(|_| message::Error::InvalidLength)?;
+        bytes.copy_from_slice(&value.to_le_bytes());
+    } else {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+fn unzigzag(value: u32) -> i32 {
+    (value >> 1) as i32 ^ -((value & 1) as i32)
+}
+
+fn write_varint(mut value: u32, buf: &mut [u8], len: &mut usize) -> Result<(), frame::Error> {
+    loop {
+        let byte = buf.get_mut(*len).ok_or(frame::Error::PayloadTooLarge)?;
+        *len += 1;
+        if value < 0x80 {
+            *byte = value as u8;
+            return Ok(());
+        }
+        *byte = value as u8 | 0x80;
+        value >>= 7;
+    }
+}
+
+/// Reads a varint from the start of `bytes`, advancing past it
+fn read_varint(bytes: &mut &[u8]) -> Result<u32, message::Error> {
+    let mut value = 0u32;
+    for i in 0..MAX_VARINT_SIZE {
+        let (&byte, rest) = bytes.split_first().ok_or(message::Error::InvalidLength)?;
+        *bytes = rest;
+        // The last byte only has room for the top 4 bits
+        if i == MAX_VARINT_SIZE - 1 && byte > 0x0F {
+            return Err(message::Error::InvalidLength);
+        }
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    unreachable!("The last byte never has its continuation bit set")
+}
@@ -0,0 +1,253 @@
+//! Recordings of the byte stream sent over the link, for host tools
+//!
+//! A capture file is a header followed by records, each holding bytes read
+//! from the link along with when they were read:
+//!
+//! ```text
+//! header: | "LCAP" | version (u16) |
+//! record: | timestamp (u64) | len (u16) | bytes (len) ... |
+//! ```
+//!
+//! Numbers are little endian, timestamps are microseconds since the capture
+//! started. Records hold the bytes exactly as they were read, whether that's
+//! a frame, part of one or garbage, so that replaying a capture through
+//! [`Messages`] reproduces what the receiver saw, errors included. Bytes read
+//! live are decoded the same way, as records that were never written.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+use crate::frame::{DecodeError, Decoder};
+use crate::message::Message;
+
+/// Start of every capture file
+pub const MAGIC: [u8; 4] = *b"LCAP";
+/// Version of the format written
+pub const VERSION: u16 = 1;
+
+/// Error reading or writing a capture file
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// File doesn't start with [`MAGIC`]
+    NotACapture,
+    /// File was written in a version of the format this one can't read
+    UnsupportedVersion(u16),
+    /// File ends in the middle of a record
+    Truncated,
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::NotACapture => f.write_str("not a capture file"),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported capture version {}", version)
+            }
+            Error::Truncated => f.write_str("capture ends in the middle of a record"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes read from the link at some point
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// Microseconds since the capture started
+    pub timestamp: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// Writes a capture file
+pub struct Writer<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Starts a capture, writing the header right away
+    pub fn new(mut inner: W) -> Result<Self, Error> {
+        inner.write_all(&MAGIC)?;
+        inner.write_all(&VERSION.to_le_bytes())?;
+        Ok(Writer { inner })
+    }
+
+    /// Records `bytes` read at `timestamp`, split across several records if
+    /// they don't fit in one, nothing is recorded if they're empty
+    pub fn write(&mut self, timestamp: u64, bytes: &[u8]) -> Result<(), Error> {
+        for chunk in bytes.chunks(u16::MAX as usize) {
+            self.inner.write_all(&timestamp.to_le_bytes())?;
+            self.inner.write_all(&(chunk.len() as u16).to_le_bytes())?;
+            self.inner.write_all(chunk)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.inner.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Reads a capture file record by record
+pub struct Reader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> Reader<R> {
+    /// Checks the header of a capture
+    pub fn new(mut inner: R) -> Result<Self, Error> {
+        let mut header = [0; 6];
+        read_exact(&mut inner, &mut header)?.ok_or(Error::NotACapture)?;
+        if header[..4] != MAGIC {
+            return Err(Error::NotACapture);
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        Ok(Reader { inner })
+    }
+
+    /// Reads the next record, `None` at the end of the capture
+    pub fn read_record(&mut self) -> Result<Option<Record>, Error> {
+        let mut header = [0; 10];
+        if read_exact(&mut self.inner, &mut header)?.is_none() {
+            return Ok(None);
+        }
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(&header[..8]);
+        let len = u16::from_le_bytes([header[8], header[9]]);
+
+        let mut bytes = std::vec![0; len as usize];
+        read_exact(&mut self.inner, &mut bytes)?.ok_or(Error::Truncated)?;
+        Ok(Some(Record {
+            timestamp: u64::from_le_bytes(timestamp),
+            bytes,
+        }))
+    }
+
+    /// Messages in the capture, decoded the way the firmware decodes them
+    pub fn messages(self) -> Messages<Self> {
+        self.messages_with(Decoder::new())
+    }
+
+    /// Messages in the capture, decoded by `decoder`, e.g. one expecting
+    /// authenticated frames
+    pub fn messages_with(self, decoder: Decoder) -> Messages<Self> {
+        Messages::new(self, decoder)
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Fills `buf`, returning `None` if the reader was already at its end and an
+/// error if it ends before `buf` is full
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<Option<()>, Error> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(Error::Truncated),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(Some(()))
+}
+
+/// Outcome of decoding a frame from a capture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decoded {
+    /// Timestamp of the record holding the byte that completed the frame, or
+    /// of the last record for frames only found at the end of the capture
+    pub timestamp: u64,
+    pub message: Result<Message, DecodeError>,
+}
+
+/// Iterator over the messages in a capture, see [`Reader::messages`], or in
+/// any other source of records
+pub struct Messages<I> {
+    records: I,
+    decoder: Decoder,
+    /// Record being decoded and how much of it was
+    record: Option<Record>,
+    pos: usize,
+}
+
+impl<I: Iterator<Item = Result<Record, Error>>> Messages<I> {
+    /// Messages in `records`, decoded by `decoder`
+    pub fn new(records: I, decoder: Decoder) -> Self {
+        Messages {
+            records,
+            decoder,
+            record: None,
+            pos: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Record, Error>>> Iterator for Messages<I> {
+    type Item = Result<Decoded, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let timestamp = self.record.as_ref().map_or(0, |record| record.timestamp);
+            // Frames already buffered go first, like one found inside a
+            // rejected frame
+            if let Some(message) = self.decoder.poll().transpose() {
+                return Some(Ok(Decoded { timestamp, message }));
+            }
+
+            let record = match &self.record {
+                Some(record) if self.pos < record.bytes.len() => record,
+                _ => match self.records.next() {
+                    Some(Ok(record)) => {
+                        self.pos = 0;
+                        self.record.insert(record)
+                    }
+                    // Nothing will complete the frame being received anymore
+                    None => {
+                        let message = self.decoder.finish().transpose()?;
+                        return Some(Ok(Decoded { timestamp, message }));
+                    }
+                    Some(Err(e)) => return Some(Err(e)),
+                },
+            };
+
+            let byte = record.bytes[self.pos];
+            self.pos += 1;
+            if let Some(message) = self.decoder.push(byte).transpose() {
+                return Some(Ok(Decoded {
+                    timestamp: record.timestamp,
+                    message,
+                }));
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Consistent Overhead Byte Stuffing framing
+//!
+//! An alternative to the [header/CRC frames](crate::frame). The message kind,
+//! payload and a CRC-16 are COBS encoded, which removes every `0x00` from
+//! them, and a single `0x00` is appended as the frame delimiter:
+//!
+//! ```text
+//! +--------------------------------------------+------+
+//! | COBS(kind | payload ... | crc16 (LE))      | 0x00 |
+//! +--------------------------------------------+------+
+//! ```
+//!
+//! The receiver resynchronizes on the next `0x00` no matter what got lost or
+//! corrupted before it. Stuffing costs at most one byte every 254.
+//!
+//! Nothing here allocates, all buffers are provided by the caller.
+
+use crate::crc::crc16;
+use crate::frame::{CRC_SIZE, MAX_PAYLOAD};
+use crate::message::{self, Message};
+use crate::validate::ValidationError;
+use crate::wire::WireMessage;
+
+/// Frame delimiter
+pub const DELIMITER: u8 = 0x00;
+
+/// Largest unencoded frame contents: kind, payload and CRC
+const MAX_RAW_SIZE: usize = 1 + MAX_PAYLOAD + CRC_SIZE;
+
+/// Size of the largest encoded frame, including its delimiter
+pub const MAX_FRAME_SIZE: usize = max_encoded_len(MAX_RAW_SIZE) + 1;
+
+/// COBS error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Output buffer can't hold the result
+    BufferTooSmall,
+    /// Input isn't valid COBS data
+    Malformed,
+}
+
+/// Error found while decoding a stream of COBS frames
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Frame isn't valid COBS data or is too short to hold a message
+    Malformed,
+    /// Frame didn't fit in the decoder's buffer
+    Oversize,
+    /// Checksum doesn't match the frame's contents
+    BadCrc,
+    /// Frame kind doesn't name any message
+    UnknownKind(u8),
+    /// Payload length doesn't match the message kind
+    InvalidLength,
+    /// Payload holds a value the message doesn't allow
+    Invalid(ValidationError),
+}
+
+impl From<message::Error> for DecodeError {
+    fn from(e: message::Error) -> Self {
+        match e {
+            message::Error::UnknownKind(kind) => DecodeError::UnknownKind(kind),
+            message::Error::InvalidLength => DecodeError::InvalidLength,
+            message::Error::Invalid(e) => DecodeError::Invalid(e),
+        }
+    }
+}
+
+/// Worst case size of `len` bytes once encoded, without the delimiter
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// COBS encodes `input` into `output`, returning the number of bytes written
+///
+/// The delimiter isn't appended.
+pub fn encode(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
+    let mut code_idx = 0;
+    let mut code = 1u8;
+    let mut out = 1;
+
+    for (i, &byte) in input.iter().enumerate() {
+        if byte == 0 {
+            *output.get_mut(code_idx).ok_or(Error::BufferTooSmall)? = code;
+            code_idx = out;
+            out += 1;
+            code = 1;
+            continue;
+        }
+
+        *output.get_mut(out).ok_or(Error::BufferTooSmall)? = byte;
+        out += 1;
+        code += 1;
+
+        // Block is full, a new one is only needed if there's data left
+        if code == 0xFF && i + 1 < input.len() {
+            *output.get_mut(code_idx).ok_or(Error::BufferTooSmall)? = code;
+            code_idx = out;
+            out += 1;
+            code = 1;
+        }
+    }
+
+    *output.get_mut(code_idx).ok_or(Error::BufferTooSmall)? = code;
+    Ok(out)
+}
+
+/// Decodes COBS data in place, returning the length of the decoded data
+///
+/// `buf` must not include the delimiter.
+pub fn decode_in_place(buf: &mut [u8]) -> Result<usize, Error> {
+    let mut read = 0;
+    let mut write = 0;
+
+    while read < buf.len() {
+        let code = buf[read];
+        if code == 0 {
+            return Err(Error::Malformed);
+        }
+        read += 1;
+
+        for _ in 1..code {
+            let byte = *buf.get(read).ok_or(Error::Malformed)?;
+            if byte == 0 {
+                return Err(Error::Malformed);
+            }
+            // Decoded data is never longer than the data read so far
+            buf[write] = byte;
+            write += 1;
+            read += 1;
+        }
+
+        if code != 0xFF && read < buf.len() {
+            buf[write] = 0;
+            write += 1;
+        }
+    }
+
+    Ok(write)
+}
+
+/// Decodes COBS data from `input` into `output`, returning the length of the
+/// decoded data
+///
+/// `input` must not include the delimiter. Since decoding is done in place,
+/// `output` must be at least as long as `input`.
+pub fn decode(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
+    let output = output.get_mut(..input.len()).ok_or(Error::BufferTooSmall)?;
+    output.copy_from_slice(input);
+    decode_in_place(output)
+}
+
+/// Writes a message as a delimited COBS frame into `buf`, returning the number
+/// of bytes written
+pub fn encode_message(message: &Message, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut raw = [0; MAX_RAW_SIZE];
+    let len = message
+        .encode_into(&mut raw[..Message::SIZE])
+        .map_err(|_| Error::BufferTooSmall)?;
+    let crc = crc16(&raw[..len]);
+    raw[len..len + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+    let n = encode(&raw[..len + CRC_SIZE], buf)?;
+    *buf.get_mut(n).ok_or(Error::BufferTooSmall)? = DELIMITER;
+    Ok(n + 1)
+}
+
+/// Parses the contents of a frame, without its delimiter, decoding in place
+pub fn decode_message(buf: &mut [u8]) -> Result<Message, DecodeError> {
+    let len = decode_in_place(buf).map_err(|_| DecodeError::Malformed)?;
+    if len < 1 + CRC_SIZE {
+        return Err(DecodeError::Malformed);
+    }
+
+    let (raw, crc) = buf[..len].split_at(len - CRC_SIZE);
+    if crc != crc16(raw).to_le_bytes() {
+        return Err(DecodeError::BadCrc);
+    }
+
+    Ok(Message::decode(raw)?)
+}
+
+/// Streaming COBS frame decoder fed one byte at a time
+///
+/// Encoded bytes are buffered in a caller-provided buffer until a delimiter
+/// arrives. A buffer of [`MAX_FRAME_SIZE`] bytes fits any frame.
+pub struct Decoder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    overflowed: bool,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Decoder {
+            buf,
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Discards any partially received frame
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.overflowed = false;
+    }
+
+    /// Feeds a byte into the decoder
+    ///
+    /// Returns `Ok(None)` while more bytes are needed to complete a frame,
+    /// `Ok(Some(_))` once a valid message has been received and `Err(_)` when
+    /// a frame is rejected.
+    pub fn push(&mut self, byte: u8) -> Result<Option<Message>, DecodeError> {
+        if byte != DELIMITER {
+            if self.len < self.buf.len() {
+                self.buf[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.overflowed = true;
+            }
+            return Ok(None);
+        }
+
+        let len = self.len;
+        let overflowed = self.overflowed;
+        self.reset();
+
+        if overflowed {
+            Err(DecodeError::Oversize)
+        } else if len == 0 {
+            // Back to back delimiters are used to flush the line
+            Ok(None)
+        } else {
+            decode_message(&mut self.buf[..len]).map(Some)
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Commands sent by the Raspberry Pi to the Nucleo
+//!
+//! Every [`Command`] is answered with a [`Response`] naming the command's
+//! [`Opcode`] and whether it was carried out.
+
+use crate::validate::{Validate, ValidationError};
+use crate::wire::WireMessage;
+
+/// Accelerometer output data rates that can be requested
+///
+/// Rates above 833 Hz aren't offered, the UART link can't keep up with them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum AccelOdr {
+    Hz12_5 = 0,
+    Hz26 = 1,
+    Hz52 = 2,
+    Hz104 = 3,
+    Hz208 = 4,
+    Hz416 = 5,
+    Hz833 = 6,
+}
+
+/// Brightest display setting
+pub const MAX_BRIGHTNESS: u8 = 15;
+
+/// Defines [`Command`] and its [`Opcode`] from a single list, so a command
+/// is added in one place
+macro_rules! commands {
+    ($($(#[$doc:meta])* $name:ident $(($arg:ty))? = $opcode:tt,)*) => {
+        /// Request for the Nucleo to do something
+        ///
+        /// The payload is the [`Opcode`] followed by the command's argument,
+        /// if it has one.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[wire(validate)]
+        #[repr(u8)]
+        pub enum Command {
+            $($(#[$doc])* $name $(($arg))? = $opcode,)*
+        }
+
+        /// Identifies a command, used by responses to refer to it
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[repr(u8)]
+        pub enum Opcode {
+            $($name = $opcode,)*
+        }
+
+        impl Command {
+            pub fn opcode(&self) -> Opcode {
+                match self {
+                    $(Command::$name { .. } => Opcode::$name,)*
+                }
+            }
+        }
+    };
+}
+
+commands! {
+    /// Changes the accelerometer's output data rate
+    SetAccelOdr(AccelOdr) = 0x01,
+    /// Changes the display brightness, from 0 (dimmest) to
+    /// [`MAX_BRIGHTNESS`]
+    SetBrightness(u8) = 0x02,
+    /// Asks for a single set of readings, even when not streaming
+    RequestSample = 0x03,
+    /// Starts sending readings continuously, the default after reset
+    StartStreaming = 0x04,
+    /// Stops sending readings continuously
+    StopStreaming = 0x05,
+    /// Asks for the device's [`Descriptor`](crate::descriptor::Descriptor)
+    RequestDescriptor = 0x06,
+    /// Streams a [`Stats`](crate::stats::Stats) per channel every given
+    /// number of seconds instead of every reading, 0 goes back to every
+    /// reading
+    SetSummaryPeriod(u8) = 0x07,
+}
+
+impl Validate for Command {
+    fn validate(&self) -> Result<(), ValidationError> {
+        match *self {
+            Command::SetBrightness(level) if level > MAX_BRIGHTNESS => {
+                Err(ValidationError::OutOfRange)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Outcome of a command
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum Status {
+    /// Command was carried out
+    Ack = 0,
+    /// Command's argument is out of range
+    InvalidArgument = 1,
+    /// Command couldn't be carried out, e.g. the sensor didn't respond
+    Failed = 2,
+    /// Command isn't supported by the device
+    Unsupported = 3,
+}
+
+/// Answer to a [`Command`]
+///
+/// The payload is the command's [`Opcode`] and the [`Status`], both `u8`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Response {
+    pub opcode: Opcode,
+    pub status: Status,
+}
+
+impl Response {
+    pub fn new(command: &Command, status: Status) -> Self {
+        Response {
+            opcode: command.opcode(),
+            status,
+        }
+    }
+
+    pub fn ack(command: &Command) -> Self {
+        Self::new(command, Status::Ack)
+    }
+
+    pub fn is_ack(&self) -> bool {
+        self.status == Status::Ack
+    }
+}
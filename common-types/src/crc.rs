@@ -0,0 +1,50 @@
+//! CRC-16/CCITT-FALSE (poly `0x1021`, init `0xFFFF`, no reflection, no xorout)
+
+const POLY: u16 = 0x1021;
+const INIT: u16 = 0xFFFF;
+
+/// Incremental CRC-16 calculation
+#[derive(Clone, Copy, Debug)]
+pub struct Crc16(u16);
+
+impl Crc16 {
+    pub const fn new() -> Self {
+        Crc16(INIT)
+    }
+
+    /// Feeds a single byte into the checksum
+    pub fn update_byte(&mut self, byte: u8) {
+        self.0 ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            if self.0 & 0x8000 != 0 {
+                self.0 = (self.0 << 1) ^ POLY;
+            } else {
+                self.0 <<= 1;
+            }
+        }
+    }
+
+    /// Feeds a slice of bytes into the checksum
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.update_byte(byte);
+        }
+    }
+
+    pub fn finish(self) -> u16 {
+        self.0
+    }
+}
+
+impl Default for Crc16 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the CRC-16 of a whole slice
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = Crc16::new();
+    crc.update(bytes);
+    crc.finish()
+}
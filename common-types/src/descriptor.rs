@@ -0,0 +1,70 @@
+//! What a device is and what it can do
+//!
+//! Several Nucleo boards may take turns on the link. A [`Descriptor`] tells
+//! the Raspberry Pi which one it's talking to, from the ID burnt into its MCU,
+//! and what it's fitted with. It's sent in answer to
+//! [`Command::RequestDescriptor`](crate::command::Command::RequestDescriptor).
+
+use crate::command::AccelOdr;
+use crate::wire::WireMessage;
+
+/// Board a device runs on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum BoardType {
+    Unknown = 0,
+    NucleoH745ZiQ = 1,
+}
+
+/// Part number of the sensor a device reads
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum SensorPart {
+    /// No sensor answered
+    Absent = 0,
+    /// ST ISM330DHCX accelerometer and gyroscope
+    Ism330dhcx = 1,
+}
+
+/// Set of accelerometer output data rates, one bit per [`AccelOdr`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OdrSet(pub u8);
+
+impl OdrSet {
+    pub const EMPTY: OdrSet = OdrSet(0);
+
+    /// Every rate [`AccelOdr`] names
+    pub const fn all() -> Self {
+        OdrSet((1 << (AccelOdr::Hz833 as u8 + 1)) - 1)
+    }
+
+    pub const fn with(self, odr: AccelOdr) -> Self {
+        OdrSet(self.0 | 1 << odr as u8)
+    }
+
+    pub const fn contains(self, odr: AccelOdr) -> bool {
+        self.0 & 1 << odr as u8 != 0
+    }
+}
+
+/// Identity and capabilities of a device
+///
+/// The payload is the 12 bytes of the unique ID followed by the
+/// [`BoardType`], the [`SensorPart`], the [`OdrSet`] and whether a display is
+/// present, all `u8`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Descriptor {
+    /// ID unique to the MCU, as read from it
+    pub unique_id: [u8; 12],
+    pub board: BoardType,
+    pub sensor: SensorPart,
+    /// Rates [`Command::SetAccelOdr`](crate::command::Command::SetAccelOdr)
+    /// accepts
+    pub accel_odrs: OdrSet,
+    /// Whether the display answered when the device started
+    pub display: bool,
+}
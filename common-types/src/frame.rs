@@ -0,0 +1,145 @@
+//! Framing of messages sent over the UART link
+//!
+//! Every message travels inside a frame with the following layout:
+//!
+//! ```text
+//! +------+------+------+-----+-------------------+------------+
+//! | 0xAA | 0x55 | kind | len | payload (len) ... | crc16 (LE) |
+//! +------+------+------+-----+-------------------+------------+
+//! ```
+//!
+//! - `kind` is the [`MessageKind`] tag of the payload.
+//! - `len` is the payload length in bytes, at most [`MAX_PAYLOAD`].
+//! - `crc16` is the [CRC-16/CCITT-FALSE](crate::crc) of `kind`, `len` and the
+//!   payload.
+//!
+//! If a byte gets lost or corrupted the checksum fails and the decoder skips
+//! ahead to the next sync marker, so the receiver realigns on the next good
+//! frame. [`Decoder`] does the same one byte at a time.
+
+use crate::crc::crc16;
+use crate::message::MessageKind;
+
+mod decoder;
+pub use decoder::{DecodeError, Decoder};
+
+/// Start of frame marker
+pub const SYNC: [u8; 2] = [0xAA, 0x55];
+/// Sync marker, kind and length
+pub const HEADER_SIZE: usize = SYNC.len() + 2;
+pub const CRC_SIZE: usize = 2;
+/// Largest payload a frame may carry
+pub const MAX_PAYLOAD: usize = 128;
+/// Size of a frame carrying a payload of [`MAX_PAYLOAD`] bytes
+pub const MAX_FRAME_SIZE: usize = HEADER_SIZE + MAX_PAYLOAD + CRC_SIZE;
+
+/// Largest frame sent or received, including its tag if it's authenticated
+#[cfg(not(feature = "auth"))]
+pub(crate) const MAX_WIRE_SIZE: usize = MAX_FRAME_SIZE;
+#[cfg(feature = "auth")]
+pub(crate) const MAX_WIRE_SIZE: usize = crate::auth::MAX_FRAME_SIZE;
+
+/// Frame encoding error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Output buffer can't hold the whole frame
+    BufferTooSmall,
+    /// Payload is longer than [`MAX_PAYLOAD`]
+    PayloadTooLarge,
+}
+
+/// Returns the size of a frame carrying `payload_len` bytes
+pub const fn frame_size(payload_len: usize) -> usize {
+    HEADER_SIZE + payload_len + CRC_SIZE
+}
+
+/// Writes a frame carrying `payload` into `buf`, returning the number of
+/// bytes written
+pub fn encode(kind: MessageKind, payload: &[u8], buf: &mut [u8]) -> Result<usize, Error> {
+    if payload.len() > MAX_PAYLOAD {
+        return Err(Error::PayloadTooLarge);
+    }
+
+    let size = frame_size(payload.len());
+    if buf.len() < size {
+        return Err(Error::BufferTooSmall);
+    }
+
+    buf[..SYNC.len()].copy_from_slice(&SYNC);
+    buf[2] = kind.tag();
+    buf[3] = payload.len() as u8;
+    buf[HEADER_SIZE..HEADER_SIZE + payload.len()].copy_from_slice(payload);
+
+    let crc = crc16(&buf[SYNC.len()..HEADER_SIZE + payload.len()]);
+    buf[size - CRC_SIZE..size].copy_from_slice(&crc.to_le_bytes());
+
+    Ok(size)
+}
+
+/// A valid frame found in a byte stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// Raw [`MessageKind`] tag, it isn't checked against the known kinds
+    pub kind: u8,
+    pub payload: &'a [u8],
+}
+
+/// Outcome of [`decode`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decoded<'a> {
+    /// A valid frame was found, the first `consumed` bytes of the input
+    /// (garbage before it and the frame itself) can be dropped
+    Frame { frame: Frame<'a>, consumed: usize },
+    /// There's no complete frame in the input yet, the first `discard` bytes
+    /// can never be part of one and can be dropped
+    Incomplete { discard: usize },
+}
+
+/// Looks for the first valid frame in `buf`
+///
+/// Bytes preceding a sync marker and frames with a bad checksum or length are
+/// skipped, which resynchronizes the stream on the next good frame.
+pub fn decode(buf: &[u8]) -> Decoded<'_> {
+    let mut start = 0;
+
+    while start < buf.len() {
+        let rest = &buf[start..];
+
+        // Check the part of the sync marker that's already been received
+        let sync_len = rest.len().min(SYNC.len());
+        if rest[..sync_len] != SYNC[..sync_len] {
+            start += 1;
+            continue;
+        }
+        if rest.len() < HEADER_SIZE {
+            return Decoded::Incomplete { discard: start };
+        }
+
+        let len = rest[3] as usize;
+        if len > MAX_PAYLOAD {
+            start += 1;
+            continue;
+        }
+
+        let size = frame_size(len);
+        if rest.len() < size {
+            return Decoded::Incomplete { discard: start };
+        }
+
+        let crc = crc16(&rest[SYNC.len()..HEADER_SIZE + len]);
+        if rest[size - CRC_SIZE..size] != crc.to_le_bytes() {
+            start += 1;
+            continue;
+        }
+
+        return Decoded::Frame {
+            frame: Frame {
+                kind: rest[2],
+                payload: &rest[HEADER_SIZE..HEADER_SIZE + len],
+            },
+            consumed: start + size,
+        };
+    }
+
+    Decoded::Incomplete { discard: start }
+}
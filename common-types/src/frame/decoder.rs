@@ -0,0 +1,223 @@
+use super::*;
+#[cfg(feature = "auth")]
+use crate::auth::{self, Key};
+#[cfg(feature = "auth")]
+use crate::message::MessageKind;
+use crate::message::{self, Message};
+use crate::validate::ValidationError;
+
+/// Error found while decoding a stream of frames
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Checksum doesn't match the frame's contents
+    BadCrc,
+    /// Frame isn't followed by the tag its contents and the key give, see
+    /// the `auth` module
+    BadTag,
+    /// Frame kind doesn't name any message
+    UnknownKind(u8),
+    /// Header announces a payload longer than [`MAX_PAYLOAD`]
+    Oversize(u8),
+    /// Payload length doesn't match the message kind
+    InvalidLength,
+    /// Payload holds a value the message doesn't allow
+    Invalid(ValidationError),
+}
+
+impl From<message::Error> for DecodeError {
+    fn from(e: message::Error) -> Self {
+        match e {
+            message::Error::UnknownKind(kind) => DecodeError::UnknownKind(kind),
+            message::Error::InvalidLength => DecodeError::InvalidLength,
+            message::Error::Invalid(e) => DecodeError::Invalid(e),
+        }
+    }
+}
+
+/// Streaming frame decoder fed one byte at a time
+///
+/// It doesn't allocate, every byte of the frame being received is kept in an
+/// internal buffer of [`MAX_FRAME_SIZE`] bytes, or a tag more with the `auth`
+/// feature. When a frame turns out to be bad, decoding restarts from the byte
+/// after its sync marker, so a frame hidden inside a corrupted one isn't lost:
+/// it's handed out by [`poll`](Decoder::poll), or by
+/// [`finish`](Decoder::finish) once the input ends.
+///
+/// ```
+/// # use common_types::frame::Decoder;
+/// # let read = || 0u8;
+/// let mut decoder = Decoder::new();
+/// loop {
+///     let mut result = decoder.push(read());
+///     loop {
+///         match result {
+///             Ok(Some(message)) => { /* handle message */ }
+///             Ok(None) => break, // need more bytes
+///             Err(_) => { /* report error, keep going */ }
+///         }
+///         result = decoder.poll();
+///     }
+/// #   break;
+/// }
+/// ```
+pub struct Decoder {
+    buf: [u8; MAX_WIRE_SIZE],
+    len: usize,
+    /// Key frames are authenticated with, if any
+    #[cfg(feature = "auth")]
+    key: Option<Key>,
+    /// Nonce the tags of sequenced frames have to cover, if any
+    #[cfg(feature = "auth")]
+    nonce: Option<u32>,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Decoder {
+            buf: [0; MAX_WIRE_SIZE],
+            len: 0,
+            #[cfg(feature = "auth")]
+            key: None,
+            #[cfg(feature = "auth")]
+            nonce: None,
+        }
+    }
+
+    /// Decoder that only accepts frames tagged with `key`
+    #[cfg(feature = "auth")]
+    pub fn with_key(key: Key) -> Self {
+        Decoder {
+            key: Some(key),
+            ..Decoder::new()
+        }
+    }
+
+    /// Only accepts sequenced frames whose tag also covers `nonce` from now
+    /// on, see [`auth`](crate::auth)
+    #[cfg(feature = "auth")]
+    pub(crate) fn set_nonce(&mut self, nonce: u32) {
+        self.nonce = Some(nonce);
+    }
+
+    /// Discards any partially received frame
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    /// Feeds a byte into the decoder
+    ///
+    /// Returns `Ok(None)` while more bytes are needed to complete a frame,
+    /// `Ok(Some(_))` once a valid message has been received and `Err(_)` when
+    /// a frame is rejected. Once it returns something, more frames may be
+    /// buffered already, see [`poll`](Decoder::poll).
+    pub fn push(&mut self, byte: u8) -> Result<Option<Message>, DecodeError> {
+        // There's always room for one more byte: every call that doesn't
+        // need more data drops at least one byte from the buffer
+        self.buf[self.len] = byte;
+        self.len += 1;
+        self.next(false)
+    }
+
+    /// Decodes the next frame already buffered, without feeding a byte
+    ///
+    /// The bytes of a rejected frame are scanned again for a frame hidden
+    /// inside it, which may be complete already. Call it after every
+    /// [`push`](Decoder::push) until it returns `Ok(None)` to get each of
+    /// them as soon as possible.
+    pub fn poll(&mut self) -> Result<Option<Message>, DecodeError> {
+        self.next(false)
+    }
+
+    /// Decodes what's left in the buffer at the end of the input
+    ///
+    /// Like [`poll`](Decoder::poll), but a frame that isn't complete yet is
+    /// given up on, so that frames hidden inside it aren't lost. Call it until
+    /// it returns `Ok(None)`, the decoder is empty then.
+    pub fn finish(&mut self) -> Result<Option<Message>, DecodeError> {
+        self.next(true)
+    }
+
+    /// Looks for a frame at the start of the buffer, giving up on incomplete
+    /// ones if the input ended
+    fn next(&mut self, ended: bool) -> Result<Option<Message>, DecodeError> {
+        loop {
+            if self.len == 0 {
+                return Ok(None);
+            }
+            let sync_len = self.len.min(SYNC.len());
+            if self.buf[..sync_len] != SYNC[..sync_len] {
+                self.drop_front(1);
+                continue;
+            }
+            if self.len < HEADER_SIZE {
+                if ended {
+                    self.drop_front(1);
+                    continue;
+                }
+                return Ok(None);
+            }
+
+            let len = self.buf[3];
+            if len as usize > MAX_PAYLOAD {
+                self.drop_front(1);
+                return Err(DecodeError::Oversize(len));
+            }
+
+            let frame_end = frame_size(len as usize);
+            let size = frame_end + self.tag_size();
+            if self.len < size {
+                if ended {
+                    self.drop_front(1);
+                    continue;
+                }
+                return Ok(None);
+            }
+
+            let crc = crc16(&self.buf[SYNC.len()..frame_end - CRC_SIZE]);
+            if self.buf[frame_end - CRC_SIZE..frame_end] != crc.to_le_bytes() {
+                self.drop_front(1);
+                return Err(DecodeError::BadCrc);
+            }
+
+            #[cfg(feature = "auth")]
+            if let Some(key) = &self.key {
+                let sequenced = self.buf[2] == MessageKind::Sequenced.tag();
+                let nonce = self.nonce.filter(|_| sequenced);
+                let data = &self.buf[SYNC.len()..frame_end];
+                if !key.verify_with_nonce(data, nonce, &self.buf[frame_end..size]) {
+                    self.drop_front(1);
+                    return Err(DecodeError::BadTag);
+                }
+            }
+
+            let frame = Frame {
+                kind: self.buf[2],
+                payload: &self.buf[HEADER_SIZE..frame_end - CRC_SIZE],
+            };
+            let message = Message::from_frame(&frame);
+            self.drop_front(size);
+
+            return Ok(Some(message?));
+        }
+    }
+
+    /// Bytes following every frame
+    fn tag_size(&self) -> usize {
+        #[cfg(feature = "auth")]
+        if self.key.is_some() {
+            return auth::TAG_SIZE;
+        }
+        0
+    }
+
+    fn drop_front(&mut self, n: usize) {
+        self.buf.copy_within(n..self.len, 0);
+        self.len -= n;
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,117 @@
+//! Telling a quiet peer from a dead one
+//!
+//! Both ends send a [`Heartbeat`] every so often, even when they have nothing
+//! else to say, and feed every frame they receive into a [`LinkMonitor`].
+//! When frames stop arriving the monitor first reports the link as
+//! [`Degraded`](LinkState::Degraded), then as [`Lost`](LinkState::Lost), so
+//! each end can react to its peer going away.
+//!
+//! Times are whatever tick count the caller passes in. The monitor has to be
+//! updated more often than that count wraps around.
+
+use crate::wire::WireMessage;
+
+/// Sign of life, numbered so that the peer can tell when some are missing
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heartbeat {
+    pub seq: u16,
+}
+
+/// Schedules the heartbeats sent to the peer
+pub struct HeartbeatTimer {
+    /// Ticks between heartbeats
+    period: u32,
+    last_sent: Option<u32>,
+    seq: u16,
+}
+
+impl HeartbeatTimer {
+    pub const fn new(period: u32) -> Self {
+        HeartbeatTimer {
+            period,
+            last_sent: None,
+            seq: 0,
+        }
+    }
+
+    /// Returns the heartbeat to send if one is due at `now`
+    pub fn poll(&mut self, now: u32) -> Option<Heartbeat> {
+        match self.last_sent {
+            Some(last) if now.wrapping_sub(last) < self.period => None,
+            _ => {
+                self.last_sent = Some(now);
+                let heartbeat = Heartbeat { seq: self.seq };
+                self.seq = self.seq.wrapping_add(1);
+                Some(heartbeat)
+            }
+        }
+    }
+}
+
+/// How recently the peer was heard from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkState {
+    /// Frames keep arriving
+    Connected,
+    /// Nothing arrived for a while, a few heartbeats may have been lost
+    Degraded,
+    /// Nothing arrived for long enough that the peer is assumed to be gone,
+    /// or nothing ever arrived
+    Lost,
+}
+
+/// Tracks the [`LinkState`] from the times frames are received
+pub struct LinkMonitor {
+    degraded_after: u32,
+    lost_after: u32,
+    last_received: u32,
+    state: LinkState,
+}
+
+impl LinkMonitor {
+    /// The link degrades after `degraded_after` ticks without a frame and is
+    /// lost after `lost_after` ticks
+    pub const fn new(degraded_after: u32, lost_after: u32) -> Self {
+        LinkMonitor {
+            degraded_after,
+            lost_after,
+            last_received: 0,
+            state: LinkState::Lost,
+        }
+    }
+
+    pub fn state(&self) -> LinkState {
+        self.state
+    }
+
+    /// Records a valid frame received at `now`, returning the new state if it
+    /// changed
+    pub fn received(&mut self, now: u32) -> Option<LinkState> {
+        self.last_received = now;
+        self.set_state(LinkState::Connected)
+    }
+
+    /// Checks how long the peer has been quiet at `now`, returning the new
+    /// state if it changed
+    pub fn update(&mut self, now: u32) -> Option<LinkState> {
+        let quiet = now.wrapping_sub(self.last_received);
+        let state = match self.state {
+            // Only a received frame brings the link back
+            LinkState::Lost => LinkState::Lost,
+            _ if quiet >= self.lost_after => LinkState::Lost,
+            _ if quiet >= self.degraded_after => LinkState::Degraded,
+            state => state,
+        };
+        self.set_state(state)
+    }
+
+    fn set_state(&mut self, state: LinkState) -> Option<LinkState> {
+        if state == self.state {
+            None
+        } else {
+            self.state = state;
+            Some(state)
+        }
+    }
+}
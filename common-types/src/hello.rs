@@ -0,0 +1,211 @@
+//! Protocol version handshake
+//!
+//! Both ends send a [`Hello`] at startup. The Nucleo answers each one it
+//! receives with its own, while the Raspberry Pi sends its own again
+//! periodically until it gets an answer, so whichever boots last still learns
+//! about its peer. Only one end answers, otherwise they would keep answering
+//! each other forever.
+//!
+//! A peer with a different major protocol version can't be understood at all,
+//! while one with the same major version but fewer supported messages can
+//! still be used for the messages both ends know about.
+
+use crate::frame;
+use crate::message::{self, write_payload, MessageKind};
+use crate::wire::WireMessage;
+
+/// Version of the wire format defined by this crate
+///
+/// The major version changes when the encoding of an existing message
+/// changes, the minor version when messages are added.
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion { major: 2, minor: 1 };
+
+/// Size of a [`Hello`] payload
+pub const SIZE: usize = 2 + 3 + 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProtocolVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+/// Firmware version of a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl Version {
+    pub const fn new(major: u8, minor: u8, patch: u8) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+/// Parses a decimal version component, used by [`firmware_version!`]
+#[doc(hidden)]
+pub const fn parse_component(s: &str) -> u8 {
+    let bytes = s.as_bytes();
+    let mut value = 0u8;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(bytes[i].is_ascii_digit(), "Invalid version component");
+        value = value * 10 + (bytes[i] - b'0');
+        i += 1;
+    }
+    value
+}
+
+/// Expands to the [`Version`] of the crate being compiled, as set in its
+/// `Cargo.toml`
+#[macro_export]
+macro_rules! firmware_version {
+    () => {
+        $crate::hello::Version::new(
+            $crate::hello::parse_component(env!("CARGO_PKG_VERSION_MAJOR")),
+            $crate::hello::parse_component(env!("CARGO_PKG_VERSION_MINOR")),
+            $crate::hello::parse_component(env!("CARGO_PKG_VERSION_PATCH")),
+        )
+    };
+}
+
+/// Set of message kinds, one bit per [`MessageKind`] tag
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KindSet(pub u32);
+
+impl KindSet {
+    pub const EMPTY: KindSet = KindSet(0);
+
+    /// Every message kind known by this build
+    pub const fn all() -> Self {
+        let mut set = KindSet::EMPTY;
+        let mut i = 0;
+        while i < MessageKind::ALL.len() {
+            set = set.with(MessageKind::ALL[i]);
+            i += 1;
+        }
+        set
+    }
+
+    pub const fn with(self, kind: MessageKind) -> Self {
+        KindSet(self.0 | 1 << kind as u8)
+    }
+
+    pub const fn contains(self, kind: MessageKind) -> bool {
+        self.0 & 1 << kind as u8 != 0
+    }
+
+    pub const fn intersection(self, other: KindSet) -> Self {
+        KindSet(self.0 & other.0)
+    }
+}
+
+/// How well a peer can be understood, see [`Hello::check`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compatibility {
+    /// Peer supports every message this build supports
+    Full,
+    /// Peer only supports some messages, only those in `common` can be used
+    Degraded { common: KindSet },
+    /// Peer speaks a different major protocol version, nothing it sends can
+    /// be trusted
+    Incompatible,
+}
+
+impl Compatibility {
+    /// Messages that can be exchanged with the peer
+    pub fn usable(self) -> KindSet {
+        match self {
+            Compatibility::Full => KindSet::all(),
+            Compatibility::Degraded { common } => common,
+            // The handshake always has to be possible
+            Compatibility::Incompatible => KindSet::EMPTY.with(MessageKind::Hello),
+        }
+    }
+}
+
+/// Capabilities announced by a device
+///
+/// The payload is the protocol version (major and minor `u8`), the firmware
+/// version (major, minor and patch `u8`) and the supported message kinds as a
+/// little endian `u32` [`KindSet`]. Its layout must never change, so any
+/// version can read it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hello {
+    pub protocol: ProtocolVersion,
+    pub firmware: Version,
+    pub kinds: KindSet,
+}
+
+impl Hello {
+    /// Announces this build of the protocol, supporting every message kind
+    pub const fn new(firmware: Version) -> Self {
+        Hello {
+            protocol: PROTOCOL_VERSION,
+            firmware,
+            kinds: KindSet::all(),
+        }
+    }
+
+    /// Checks what can be exchanged with a peer that sent `remote`
+    pub fn check(&self, remote: &Hello) -> Compatibility {
+        if self.protocol.major != remote.protocol.major {
+            return Compatibility::Incompatible;
+        }
+
+        let common = self.kinds.intersection(remote.kinds);
+        if common == self.kinds {
+            Compatibility::Full
+        } else {
+            Compatibility::Degraded { common }
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; SIZE] {
+        let mut bytes = [0; SIZE];
+        bytes[0] = self.protocol.major;
+        bytes[1] = self.protocol.minor;
+        bytes[2] = self.firmware.major;
+        bytes[3] = self.firmware.minor;
+        bytes[4] = self.firmware.patch;
+        bytes[5..9].copy_from_slice(&self.kinds.0.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; SIZE]) -> Self {
+        Hello {
+            protocol: ProtocolVersion {
+                major: bytes[0],
+                minor: bytes[1],
+            },
+            firmware: Version::new(bytes[2], bytes[3], bytes[4]),
+            kinds: KindSet(u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]])),
+        }
+    }
+}
+
+impl WireMessage for Hello {
+    const SIZE: usize = SIZE;
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        write_payload(buf, &self.to_bytes())
+    }
+
+    /// Fields appended by newer versions are ignored.
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        bytes
+            .get(..SIZE)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(Hello::from_bytes)
+            .ok_or(message::Error::InvalidLength)
+    }
+}
@@ -1,15 +1,39 @@
 #![no_std]
 
-use core::mem::size_of;
+#[cfg(feature = "std")]
+extern crate std;
 
-pub struct Temperature(pub f32);
+// Lets the code generated by `#[derive(WireMessage)]` be used in this crate
+extern crate self as common_types;
 
-impl Temperature {
-    pub fn to_bytes(self) -> [u8; size_of::<f32>()] {
-        self.0.to_le_bytes()
-    }
+#[cfg(feature = "auth")]
+pub mod auth;
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod capture;
+pub mod cobs;
+pub mod command;
+pub mod crc;
+pub mod descriptor;
+pub mod frame;
+pub mod heartbeat;
+pub mod hello;
+pub mod message;
+#[cfg(feature = "postcard")]
+pub mod postcard;
+pub mod reliable;
+pub mod sample;
+pub mod sensor;
+pub mod stats;
+pub mod status;
+pub mod timesync;
+pub mod units;
+pub mod update;
+pub mod validate;
+pub mod wire;
 
-    pub fn from_bytes(bytes: [u8; size_of::<f32>()]) -> Self {
-        Temperature(f32::from_le_bytes(bytes))
-    }
-}
+pub use batch::Batch;
+pub use sample::Sample;
+pub use sensor::{CentiCelsius, SensorMessage, Temperature};
+pub use units::{Celsius, Fahrenheit, Kelvin};
+pub use wire::WireMessage;
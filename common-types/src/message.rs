@@ -0,0 +1,449 @@
+use crate::batch::{Batch, DeltaBatch};
+use crate::command::{Command, Response};
+use crate::descriptor::Descriptor;
+use crate::frame::{self, Frame};
+use crate::heartbeat::Heartbeat;
+use crate::hello::Hello;
+use crate::reliable::{Ack, Sequenced};
+use crate::sample::Sample;
+use crate::sensor::SensorMessage;
+use crate::stats::Stats;
+use crate::status::DeviceStatus;
+use crate::timesync::{TimeReply, TimeRequest};
+use crate::update::{UpdateBegin, UpdateChunk, UpdateCommit, UpdateReply};
+use crate::validate::{Validate, ValidationError};
+use crate::wire::{max_size, WireMessage};
+
+/// Tag identifying the type of the payload carried by a frame
+///
+/// Tags must stay below 32 so that they fit in a
+/// [`KindSet`](crate::hello::KindSet).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum MessageKind {
+    Temperature = 0x01,
+    Acceleration = 0x02,
+    AngularRate = 0x03,
+    Sample = 0x04,
+    Hello = 0x05,
+    Command = 0x06,
+    Response = 0x07,
+    TemperatureCenti = 0x08,
+    Batch = 0x09,
+    DeltaBatch = 0x0A,
+    Sequenced = 0x0B,
+    Ack = 0x0C,
+    DeviceStatus = 0x0D,
+    Heartbeat = 0x0E,
+    TimeRequest = 0x0F,
+    TimeReply = 0x10,
+    UpdateBegin = 0x11,
+    UpdateChunk = 0x12,
+    UpdateCommit = 0x13,
+    UpdateReply = 0x14,
+    Descriptor = 0x15,
+    Stats = 0x16,
+}
+
+impl MessageKind {
+    /// Every message kind
+    pub const ALL: [MessageKind; 22] = [
+        MessageKind::Temperature,
+        MessageKind::Acceleration,
+        MessageKind::AngularRate,
+        MessageKind::Sample,
+        MessageKind::Hello,
+        MessageKind::Command,
+        MessageKind::Response,
+        MessageKind::TemperatureCenti,
+        MessageKind::Batch,
+        MessageKind::DeltaBatch,
+        MessageKind::Sequenced,
+        MessageKind::Ack,
+        MessageKind::DeviceStatus,
+        MessageKind::Heartbeat,
+        MessageKind::TimeRequest,
+        MessageKind::TimeReply,
+        MessageKind::UpdateBegin,
+        MessageKind::UpdateChunk,
+        MessageKind::UpdateCommit,
+        MessageKind::UpdateReply,
+        MessageKind::Descriptor,
+        MessageKind::Stats,
+    ];
+
+    pub fn tag(self) -> u8 {
+        self as u8
+    }
+}
+
+impl TryFrom<u8> for MessageKind {
+    type Error = u8;
+
+    /// Fails with the unknown tag if it doesn't name any message
+    fn try_from(tag: u8) -> Result<Self, u8> {
+        match tag {
+            0x01 => Ok(MessageKind::Temperature),
+            0x02 => Ok(MessageKind::Acceleration),
+            0x03 => Ok(MessageKind::AngularRate),
+            0x04 => Ok(MessageKind::Sample),
+            0x05 => Ok(MessageKind::Hello),
+            0x06 => Ok(MessageKind::Command),
+            0x07 => Ok(MessageKind::Response),
+            0x08 => Ok(MessageKind::TemperatureCenti),
+            0x09 => Ok(MessageKind::Batch),
+            0x0A => Ok(MessageKind::DeltaBatch),
+            0x0B => Ok(MessageKind::Sequenced),
+            0x0C => Ok(MessageKind::Ack),
+            0x0D => Ok(MessageKind::DeviceStatus),
+            0x0E => Ok(MessageKind::Heartbeat),
+            0x0F => Ok(MessageKind::TimeRequest),
+            0x10 => Ok(MessageKind::TimeReply),
+            0x11 => Ok(MessageKind::UpdateBegin),
+            0x12 => Ok(MessageKind::UpdateChunk),
+            0x13 => Ok(MessageKind::UpdateCommit),
+            0x14 => Ok(MessageKind::UpdateReply),
+            0x15 => Ok(MessageKind::Descriptor),
+            0x16 => Ok(MessageKind::Stats),
+            _ => Err(tag),
+        }
+    }
+}
+
+/// Error parsing a message from a frame's payload
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Frame kind doesn't name any message
+    UnknownKind(u8),
+    /// Payload length doesn't match the message kind
+    InvalidLength,
+    /// Payload holds a value the message doesn't allow
+    Invalid(ValidationError),
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        Error::Invalid(e)
+    }
+}
+
+/// Copies an encoded payload into `buf`, returning its length
+pub(crate) fn write_payload(buf: &mut [u8], bytes: &[u8]) -> Result<usize, frame::Error> {
+    let dst = buf
+        .get_mut(..bytes.len())
+        .ok_or(frame::Error::BufferTooSmall)?;
+    dst.copy_from_slice(bytes);
+    Ok(bytes.len())
+}
+
+/// Passes `value` through if it's valid
+pub(crate) fn validated<T: Validate>(value: T) -> Result<T, Error> {
+    value.validate()?;
+    Ok(value)
+}
+
+/// Any message that can be sent over the link
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Message {
+    Sensor(SensorMessage),
+    Sample(Sample<SensorMessage>),
+    Hello(Hello),
+    Command(Command),
+    Response(Response),
+    Batch(Batch),
+    DeltaBatch(DeltaBatch),
+    Sequenced(Sequenced),
+    Ack(Ack),
+    DeviceStatus(DeviceStatus),
+    Heartbeat(Heartbeat),
+    TimeRequest(TimeRequest),
+    TimeReply(TimeReply),
+    UpdateBegin(UpdateBegin),
+    UpdateChunk(UpdateChunk),
+    UpdateCommit(UpdateCommit),
+    UpdateReply(UpdateReply),
+    Descriptor(Descriptor),
+    Stats(Stats),
+}
+
+impl Message {
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Message::Sensor(reading) => reading.kind(),
+            Message::Sample(_) => MessageKind::Sample,
+            Message::Hello(_) => MessageKind::Hello,
+            Message::Command(_) => MessageKind::Command,
+            Message::Response(_) => MessageKind::Response,
+            Message::Batch(_) => MessageKind::Batch,
+            Message::DeltaBatch(_) => MessageKind::DeltaBatch,
+            Message::Sequenced(_) => MessageKind::Sequenced,
+            Message::Ack(_) => MessageKind::Ack,
+            Message::DeviceStatus(_) => MessageKind::DeviceStatus,
+            Message::Heartbeat(_) => MessageKind::Heartbeat,
+            Message::TimeRequest(_) => MessageKind::TimeRequest,
+            Message::TimeReply(_) => MessageKind::TimeReply,
+            Message::UpdateBegin(_) => MessageKind::UpdateBegin,
+            Message::UpdateChunk(_) => MessageKind::UpdateChunk,
+            Message::UpdateCommit(_) => MessageKind::UpdateCommit,
+            Message::UpdateReply(_) => MessageKind::UpdateReply,
+            Message::Descriptor(_) => MessageKind::Descriptor,
+            Message::Stats(_) => MessageKind::Stats,
+        }
+    }
+
+    /// Writes the message's payload into `buf`, returning the number of bytes
+    /// written
+    pub fn encode_payload(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        match self {
+            Message::Sensor(reading) => reading.encode_payload(buf),
+            Message::Sample(sample) => sample.encode_into(buf),
+            Message::Hello(hello) => hello.encode_into(buf),
+            Message::Command(command) => command.encode_into(buf),
+            Message::Response(response) => response.encode_into(buf),
+            Message::Batch(batch) => batch.encode_into(buf),
+            Message::DeltaBatch(batch) => batch.encode_into(buf),
+            Message::Sequenced(sequenced) => sequenced.encode_into(buf),
+            Message::Ack(ack) => ack.encode_into(buf),
+            Message::DeviceStatus(status) => status.encode_into(buf),
+            Message::Heartbeat(heartbeat) => heartbeat.encode_into(buf),
+            Message::TimeRequest(request) => request.encode_into(buf),
+            Message::TimeReply(reply) => reply.encode_into(buf),
+            Message::UpdateBegin(begin) => begin.encode_into(buf),
+            Message::UpdateChunk(chunk) => chunk.encode_into(buf),
+            Message::UpdateCommit(commit) => commit.encode_into(buf),
+            Message::UpdateReply(reply) => reply.encode_into(buf),
+            Message::Descriptor(descriptor) => descriptor.encode_into(buf),
+            Message::Stats(stats) => stats.encode_into(buf),
+        }
+    }
+
+    /// Writes the message as a framed packet into `buf`, returning the number
+    /// of bytes written
+    pub fn to_frame(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        let mut payload = [0; frame::MAX_PAYLOAD];
+        let len = self.encode_payload(&mut payload)?;
+        frame::encode(self.kind(), &payload[..len], buf)
+    }
+
+    /// Parses a payload according to its kind tag
+    pub fn from_payload(kind: u8, payload: &[u8]) -> Result<Self, Error> {
+        let kind = MessageKind::try_from(kind).map_err(Error::UnknownKind)?;
+        match kind {
+            MessageKind::Temperature
+            | MessageKind::TemperatureCenti
+            | MessageKind::Acceleration
+            | MessageKind::AngularRate => {
+                SensorMessage::from_payload(kind, payload).map(Message::Sensor)
+            }
+            MessageKind::Sample => Sample::decode(payload).map(Message::Sample),
+            MessageKind::Hello => Hello::decode(payload).map(Message::Hello),
+            MessageKind::Command => Command::decode(payload).map(Message::Command),
+            MessageKind::Response => Response::decode(payload).map(Message::Response),
+            MessageKind::Batch => Batch::decode(payload).map(Message::Batch),
+            MessageKind::DeltaBatch => DeltaBatch::decode(payload).map(Message::DeltaBatch),
+            MessageKind::Sequenced => Sequenced::decode(payload).map(Message::Sequenced),
+            MessageKind::Ack => Ack::decode(payload).map(Message::Ack),
+            MessageKind::DeviceStatus => DeviceStatus::decode(payload).map(Message::DeviceStatus),
+            MessageKind::Heartbeat => Heartbeat::decode(payload).map(Message::Heartbeat),
+            MessageKind::TimeRequest => TimeRequest::decode(payload).map(Message::TimeRequest),
+            MessageKind::TimeReply => TimeReply::decode(payload).map(Message::TimeReply),
+            MessageKind::UpdateBegin => UpdateBegin::decode(payload).map(Message::UpdateBegin),
+            MessageKind::UpdateChunk => UpdateChunk::decode(payload).map(Message::UpdateChunk),
+            MessageKind::UpdateCommit => UpdateCommit::decode(payload).map(Message::UpdateCommit),
+            MessageKind::UpdateReply => UpdateReply::decode(payload).map(Message::UpdateReply),
+            MessageKind::Descriptor => Descriptor::decode(payload).map(Message::Descriptor),
+            MessageKind::Stats => Stats::decode(payload).map(Message::Stats),
+        }
+    }
+
+    /// Parses the payload of a frame according to its kind
+    pub fn from_frame(frame: &Frame) -> Result<Self, Error> {
+        Self::from_payload(frame.kind, frame.payload)
+    }
+}
+
+/// Encoded as the [`MessageKind`] tag followed by the message's payload
+impl WireMessage for Message {
+    const SIZE: usize = 1 + max_size(&[
+        SensorMessage::SIZE - 1,
+        Sample::<SensorMessage>::SIZE,
+        Hello::SIZE,
+        Command::SIZE,
+        Response::SIZE,
+        Batch::SIZE,
+        DeltaBatch::SIZE,
+        Sequenced::SIZE,
+        Ack::SIZE,
+        DeviceStatus::SIZE,
+        Heartbeat::SIZE,
+        TimeRequest::SIZE,
+        TimeReply::SIZE,
+        UpdateBegin::SIZE,
+        UpdateChunk::SIZE,
+        UpdateCommit::SIZE,
+        UpdateReply::SIZE,
+        Descriptor::SIZE,
+        Stats::SIZE,
+    ]);
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        let (tag, payload) = buf.split_first_mut().ok_or(frame::Error::BufferTooSmall)?;
+        *tag = self.kind().tag();
+        Ok(1 + self.encode_payload(payload)?)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let (&tag, payload) = bytes.split_first().ok_or(Error::InvalidLength)?;
+        Self::from_payload(tag, payload)
+    }
+}
+
+// Every message has to fit in a frame
+const _: () = assert!(Message::SIZE <= 1 + frame::MAX_PAYLOAD);
+
+impl Validate for Message {
+    fn validate(&self) -> Result<(), ValidationError> {
+        match self {
+            Message::Sensor(reading) => reading.validate(),
+            Message::Sample(sample) => sample.validate(),
+            // Batches check their readings as they're added
+            Message::Batch(_) | Message::DeltaBatch(_) => Ok(()),
+            // Decoding the wrapped message checks it
+            Message::Sequenced(sequenced) => match sequenced.message() {
+                Err(Error::Invalid(e)) => Err(e),
+                _ => Ok(()),
+            },
+            Message::Hello(_)
+            | Message::Response(_)
+            | Message::Ack(_)
+            | Message::DeviceStatus(_)
+            | Message::Heartbeat(_)
+            | Message::TimeRequest(_)
+            | Message::UpdateCommit(_)
+            | Message::UpdateReply(_)
+            | Message::Descriptor(_) => Ok(()),
+            // The receiver checks the CRC, to tell the sender when it's wrong
+            Message::UpdateChunk(_) => Ok(()),
+            Message::TimeReply(reply) => reply.validate(),
+            Message::UpdateBegin(begin) => begin.validate(),
+            Message::Stats(stats) => stats.validate(),
+            Message::Command(command) => command.validate(),
+        }
+    }
+}
+
+impl From<SensorMessage> for Message {
+    fn from(reading: SensorMessage) -> Self {
+        Message::Sensor(reading)
+    }
+}
+
+impl From<Sample<SensorMessage>> for Message {
+    fn from(sample: Sample<SensorMessage>) -> Self {
+        Message::Sample(sample)
+    }
+}
+
+impl From<Hello> for Message {
+    fn from(hello: Hello) -> Self {
+        Message::Hello(hello)
+    }
+}
+
+impl From<Command> for Message {
+    fn from(command: Command) -> Self {
+        Message::Command(command)
+    }
+}
+
+impl From<Response> for Message {
+    fn from(response: Response) -> Self {
+        Message::Response(response)
+    }
+}
+
+impl From<Batch> for Message {
+    fn from(batch: Batch) -> Self {
+        Message::Batch(batch)
+    }
+}
+
+impl From<DeltaBatch> for Message {
+    fn from(batch: DeltaBatch) -> Self {
+        Message::DeltaBatch(batch)
+    }
+}
+
+impl From<Sequenced> for Message {
+    fn from(sequenced: Sequenced) -> Self {
+        Message::Sequenced(sequenced)
+    }
+}
+
+impl From<Ack> for Message {
+    fn from(ack: Ack) -> Self {
+        Message::Ack(ack)
+    }
+}
+
+impl From<DeviceStatus> for Message {
+    fn from(status: DeviceStatus) -> Self {
+        Message::DeviceStatus(status)
+    }
+}
+
+impl From<Heartbeat> for Message {
+    fn from(heartbeat: Heartbeat) -> Self {
+        Message::Heartbeat(heartbeat)
+    }
+}
+
+impl From<TimeRequest> for Message {
+    fn from(request: TimeRequest) -> Self {
+        Message::TimeRequest(request)
+    }
+}
+
+impl From<TimeReply> for Message {
+    fn from(reply: TimeReply) -> Self {
+        Message::TimeReply(reply)
+    }
+}
+
+impl From<UpdateBegin> for Message {
+    fn from(begin: UpdateBegin) -> Self {
+        Message::UpdateBegin(begin)
+    }
+}
+
+impl From<UpdateChunk> for Message {
+    fn from(chunk: UpdateChunk) -> Self {
+        Message::UpdateChunk(chunk)
+    }
+}
+
+impl From<UpdateCommit> for Message {
+    fn from(commit: UpdateCommit) -> Self {
+        Message::UpdateCommit(commit)
+    }
+}
+
+impl From<UpdateReply> for Message {
+    fn from(reply: UpdateReply) -> Self {
+        Message::UpdateReply(reply)
+    }
+}
+
+impl From<Descriptor> for Message {
+    fn from(descriptor: Descriptor) -> Self {
+        Message::Descriptor(descriptor)
+    }
+}
+
+impl From<Stats> for Message {
+    fn from(stats: Stats) -> Self {
+        Message::Stats(stats)
+    }
+}
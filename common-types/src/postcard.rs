@@ -0,0 +1,49 @@
+//! Postcard encoding of messages, for host tools
+//!
+//! An alternative to the hand written [`WireMessage`](crate::WireMessage)
+//! encoding, built on the `serde` implementations of the messages. Both ends
+//! of a link must agree on the encoding, the firmware only speaks the hand
+//! written one.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::validate::{Validate, ValidationError};
+
+/// Postcard encoding error
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Postcard couldn't encode or decode the value
+    Postcard(::postcard::Error),
+    /// Input is longer than the encoded value
+    TrailingBytes,
+    /// Decoded value isn't allowed by the message
+    Invalid(ValidationError),
+}
+
+impl From<::postcard::Error> for Error {
+    fn from(e: ::postcard::Error) -> Self {
+        Error::Postcard(e)
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Self {
+        Error::Invalid(e)
+    }
+}
+
+/// Encodes `value` into `buf`, returning the number of bytes written
+pub fn encode<T: Serialize>(value: &T, buf: &mut [u8]) -> Result<usize, Error> {
+    Ok(::postcard::to_slice(value, buf)?.len())
+}
+
+/// Decodes a value, rejecting the ones the message doesn't allow
+pub fn decode<T: DeserializeOwned + Validate>(bytes: &[u8]) -> Result<T, Error> {
+    let (value, rest): (T, _) = ::postcard::take_from_bytes(bytes)?;
+    if !rest.is_empty() {
+        return Err(Error::TrailingBytes);
+    }
+    value.validate()?;
+    Ok(value)
+}
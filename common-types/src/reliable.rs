@@ -0,0 +1,542 @@
+//! Delivery of messages that must not be lost
+//!
+//! Readings are streamed and a lost one is soon replaced by the next, but a
+//! lost command leaves both devices disagreeing on how they're configured. A
+//! [`Link`] sends such messages wrapped in a [`Sequenced`] message, keeps them
+//! until the peer answers with an [`Ack`] and sends them again when it
+//! doesn't:
+//!
+//! - The receiver only delivers the sequenced message it expects next and
+//!   acknowledges it. A message it already delivered is acknowledged again,
+//!   its first acknowledgement must have been lost.
+//! - A message ahead of the expected one means something in between was lost,
+//!   it's dropped and answered with a [`Ack::Nack`] for the expected message,
+//!   which the sender sends again along with everything after it.
+//! - Messages not acknowledged within a timeout are all sent again, after too
+//!   many retries they're given up on and handed back to the caller.
+//!
+//! Time is whatever tick count the caller passes in, the link never reads a
+//! clock by itself. Any other message goes through the link as is, so
+//! readings keep being streamed without acknowledgements.
+
+#[cfg(feature = "auth")]
+use crate::auth::Key;
+use crate::frame::{self, DecodeError, Decoder, MAX_PAYLOAD};
+use crate::message::{self, Message};
+use crate::validate::{Validate, ValidationError};
+use crate::wire::WireMessage;
+
+/// Room for the wrapped message, tag included
+pub const MESSAGE_SIZE: usize = MAX_PAYLOAD - 2;
+
+/// A byte stream messages are sent over, such as a UART
+pub trait Transport {
+    type Error;
+
+    /// Sends every byte of `bytes`, blocking until they're all queued
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Takes the next received byte, `None` if there's none waiting
+    fn read_byte(&mut self) -> Result<Option<u8>, Self::Error>;
+}
+
+/// A message sent reliably, along with its sequence number
+///
+/// The payload is the sequence number (`u8`), the sequence number of the
+/// oldest message the sender is still waiting an acknowledgement for
+/// (`u8`), and the wrapped message encoded as its [`MessageKind`] tag
+/// followed by its payload. Messages with payloads longer than
+/// [`MESSAGE_SIZE`] - 1 bytes, like full batches, can't be wrapped.
+///
+/// [`MessageKind`]: crate::message::MessageKind
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sequenced {
+    pub seq: u8,
+    /// Messages before this one will never be sent again, the receiver stops
+    /// waiting for them
+    pub base: u8,
+    len: usize,
+    message: [u8; MESSAGE_SIZE],
+}
+
+impl Sequenced {
+    const EMPTY: Sequenced = Sequenced {
+        seq: 0,
+        base: 0,
+        len: 0,
+        message: [0; MESSAGE_SIZE],
+    };
+
+    pub fn new(seq: u8, base: u8, message: &Message) -> Result<Self, frame::Error> {
+        let mut sequenced = Sequenced {
+            seq,
+            base,
+            ..Sequenced::EMPTY
+        };
+        sequenced.len = message
+            .encode_into(&mut sequenced.message)
+            .map_err(|_| frame::Error::PayloadTooLarge)?;
+        Ok(sequenced)
+    }
+
+    /// Decodes the wrapped message, which only fails if it was built from an
+    /// invalid one
+    pub fn message(&self) -> Result<Message, message::Error> {
+        Message::decode(&self.message[..self.len])
+    }
+}
+
+impl WireMessage for Sequenced {
+    const SIZE: usize = 2 + MESSAGE_SIZE;
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        let size = 2 + self.len;
+        let buf = buf.get_mut(..size).ok_or(frame::Error::BufferTooSmall)?;
+        buf[0] = self.seq;
+        buf[1] = self.base;
+        buf[2..].copy_from_slice(&self.message[..self.len]);
+        Ok(size)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        let [seq, base, ref message @ ..] = *bytes else {
+            return Err(message::Error::InvalidLength);
+        };
+        let message = Message::decode(message)?;
+        Sequenced::new(seq, base, &message).map_err(|_| message::Error::InvalidLength)
+    }
+}
+
+/// Serialized as its sequence numbers followed by the wrapped message
+#[cfg(feature = "serde")]
+impl serde::Serialize for Sequenced {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeStruct};
+
+        let message = self
+            .message()
+            .map_err(|_| S::Error::custom("invalid wrapped message"))?;
+        let mut state = serializer.serialize_struct("Sequenced", 3)?;
+        state.serialize_field("seq", &self.seq)?;
+        state.serialize_field("base", &self.base)?;
+        state.serialize_field("message", &message)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Sequenced {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "Sequenced")]
+        struct Raw {
+            seq: u8,
+            base: u8,
+            message: Message,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        Sequenced::new(raw.seq, raw.base, &raw.message)
+            .map_err(|_| D::Error::custom("wrapped message is too large"))
+    }
+}
+
+/// Answer to [`Sequenced`] messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ack {
+    /// Every message up to and including this one was delivered
+    Ack(u8),
+    /// This message was never received, it and everything sent after it
+    /// have to be sent again
+    Nack(u8),
+    /// Sequenced messages are only accepted if tagged for this nonce from
+    /// now on, see `Link::with_key`
+    Nonce(u32),
+}
+
+/// Something that happened on a [`Link`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// A message arrived, unwrapped if it was sent reliably
+    Received(Message),
+    /// The peer acknowledged every message sent reliably up to and including
+    /// the one with this sequence number
+    Delivered(u8),
+    /// A message sent reliably was given up on, it may or may not have
+    /// arrived
+    Failed(Message),
+}
+
+/// Error sending or receiving through a [`Link`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Transport failed to send or receive bytes
+    Transport(E),
+    /// A received frame was rejected
+    Decode(DecodeError),
+    /// Message doesn't fit in a frame
+    Frame(frame::Error),
+    /// Message holds a value it doesn't allow
+    Invalid(ValidationError),
+    /// Every slot of the retransmit window holds an unacknowledged message
+    WindowFull,
+    /// Command sent or received without being sequenced on a link with a
+    /// key
+    Unsequenced,
+}
+
+/// Both ends of a reliable link over a [`Transport`]
+///
+/// At most `WINDOW` messages sent reliably can wait for their
+/// acknowledgement at once, each one taking a frame's worth of memory.
+///
+/// Receiving a [`Hello`](crate::hello::Hello) means the peer may have
+/// restarted and numbers its messages from scratch, so the link accepts
+/// whatever sequenced message comes next. On a link with a key this only
+/// holds for messages tagged after the hello, see `Link::with_key`.
+pub struct Link<T, const WINDOW: usize> {
+    transport: T,
+    decoder: Decoder,
+    /// Ticks to wait for an acknowledgement before sending again
+    timeout: u32,
+    max_retries: u8,
+    retries: u8,
+    /// Sequence number of the next message sent reliably
+    next_seq: u8,
+    /// Messages waiting for an acknowledgement, oldest first
+    pending: [Sequenced; WINDOW],
+    len: usize,
+    /// Messages at the front of `pending` given up on and not reported yet
+    failed: usize,
+    /// Tick count when the oldest pending message was last sent
+    sent_at: u32,
+    /// Sequence number the next sequenced message should carry, `None` until
+    /// one arrives from the peer
+    expected: Option<u8>,
+    /// Key frames are authenticated with, if any
+    #[cfg(feature = "auth")]
+    key: Option<Key>,
+    /// Nonce the sequenced messages received have to be tagged for
+    #[cfg(feature = "auth")]
+    nonce: u32,
+    /// Nonce the peer asked sequenced messages to be tagged for, if known
+    #[cfg(feature = "auth")]
+    peer_nonce: Option<u32>,
+}
+
+impl<T: Transport, const WINDOW: usize> Link<T, WINDOW> {
+    // Sequence numbers only tell which of two messages was sent first while
+    // they're at most half their range apart
+    const WINDOW_FITS: () = assert!(WINDOW > 0 && WINDOW <= 128);
+
+    /// Messages sent reliably are sent again every `timeout` ticks without an
+    /// acknowledgement, up to `max_retries` times
+    pub fn new(transport: T, timeout: u32, max_retries: u8) -> Self {
+        let () = Self::WINDOW_FITS;
+        Link {
+            transport,
+            decoder: Decoder::new(),
+            timeout,
+            max_retries,
+            retries: 0,
+            next_seq: 0,
+            pending: [Sequenced::EMPTY; WINDOW],
+            len: 0,
+            failed: 0,
+            sent_at: 0,
+            expected: None,
+            #[cfg(feature = "auth")]
+            key: None,
+            #[cfg(feature = "auth")]
+            nonce: 0,
+            #[cfg(feature = "auth")]
+            peer_nonce: None,
+        }
+    }
+
+    /// Link whose frames are all authenticated with `key`, frames from the
+    /// peer without a valid tag are rejected
+    ///
+    /// A tagged frame can be recorded and sent again, so commands only go
+    /// through [`send_reliable`](Link::send_reliable), commands sent or
+    /// received as plain messages give [`Error::Unsequenced`]. Sequenced
+    /// messages are tagged for a nonce the receiver picks and sends in an
+    /// [`Ack::Nonce`], starting from `nonce`, which must be random and
+    /// different every time the device starts. The receiver picks the next
+    /// one whenever it accepts sequence numbers from scratch after a hello,
+    /// and every 128 messages before sequence numbers wrap around, so a
+    /// recorded frame is never taken for a new message. A peer whose frames
+    /// are rejected is sent the current nonce again.
+    #[cfg(feature = "auth")]
+    pub fn with_key(transport: T, timeout: u32, max_retries: u8, key: Key, nonce: u32) -> Self {
+        let mut decoder = Decoder::with_key(key.clone());
+        decoder.set_nonce(nonce);
+        Link {
+            decoder,
+            key: Some(key),
+            nonce,
+            ..Link::new(transport, timeout, max_retries)
+        }
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Sends a message once, without waiting for an acknowledgement
+    pub fn send(&mut self, message: impl Into<Message>) -> Result<(), Error<T::Error>> {
+        let message = message.into();
+        if self.must_be_sequenced(&message) {
+            return Err(Error::Unsequenced);
+        }
+        let mut buf = [0; frame::MAX_WIRE_SIZE];
+        let len = self.encode(&message, &mut buf).map_err(Error::Frame)?;
+        self.transport
+            .write_all(&buf[..len])
+            .map_err(Error::Transport)
+    }
+
+    /// Sends a message that's sent again until the peer acknowledges it,
+    /// returning its sequence number
+    ///
+    /// The message is kept even if the transport fails to send it, it goes
+    /// out again once the timeout expires.
+    pub fn send_reliable(
+        &mut self,
+        message: impl Into<Message>,
+        now: u32,
+    ) -> Result<u8, Error<T::Error>> {
+        if self.len == WINDOW {
+            return Err(Error::WindowFull);
+        }
+        let message = message.into();
+        message.validate().map_err(Error::Invalid)?;
+
+        let seq = self.next_seq;
+        let sequenced = Sequenced::new(seq, self.base(), &message).map_err(Error::Frame)?;
+        if self.len == self.failed {
+            self.sent_at = now;
+            self.retries = 0;
+        }
+        self.pending[self.len] = sequenced;
+        self.len += 1;
+        self.next_seq = seq.wrapping_add(1);
+
+        self.send(sequenced)?;
+        Ok(seq)
+    }
+
+    /// Processes received bytes and expired timeouts, returning the first
+    /// thing that happened
+    ///
+    /// Call it until it returns `Ok(None)`, a rejected frame is reported as
+    /// an error but doesn't stop the link.
+    pub fn poll(&mut self, now: u32) -> Result<Option<Event>, Error<T::Error>> {
+        if self.len > 0 && self.failed == 0 && now.wrapping_sub(self.sent_at) >= self.timeout {
+            if self.retries == self.max_retries {
+                self.failed = self.len;
+            } else {
+                self.retries += 1;
+                self.resend(0, now)?;
+            }
+        }
+
+        if self.failed > 0 {
+            let sequenced = self.pending[0];
+            self.pending[..self.len].rotate_left(1);
+            self.len -= 1;
+            self.failed -= 1;
+            let message = sequenced.message().expect("Only valid messages are sent");
+            return Ok(Some(Event::Failed(message)));
+        }
+
+        loop {
+            // Frames already buffered go first, like one found inside a
+            // rejected frame
+            let decoded = match self.decoder.poll() {
+                Ok(None) => match self.transport.read_byte().map_err(Error::Transport)? {
+                    Some(byte) => self.decoder.push(byte),
+                    None => return Ok(None),
+                },
+                decoded => decoded,
+            };
+            let event = match decoded {
+                Ok(Some(message)) => self.receive(message, now)?,
+                Ok(None) => None,
+                Err(e) => {
+                    // The peer may be tagging for an old nonce
+                    if e == DecodeError::BadTag {
+                        self.announce_nonce();
+                    }
+                    return Err(Error::Decode(e));
+                }
+            };
+            if event.is_some() {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Writes a frame carrying `message` into `buf`, tagged if the link has a
+    /// key
+    fn encode(&self, message: &Message, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        #[cfg(feature = "auth")]
+        if let Some(key) = &self.key {
+            let nonce = match message {
+                Message::Sequenced(_) => self.peer_nonce,
+                _ => None,
+            };
+            return key.encode_with_nonce(message, nonce, buf);
+        }
+        message.to_frame(buf)
+    }
+
+    /// Picks the next nonce on a link with a key, every sequenced frame
+    /// tagged until now is rejected from then on
+    fn renew_nonce(&mut self) {
+        #[cfg(feature = "auth")]
+        if self.key.is_some() {
+            self.nonce = self.nonce.wrapping_add(1);
+            self.decoder.set_nonce(self.nonce);
+            self.announce_nonce();
+        }
+    }
+
+    /// Tells the peer which nonce to tag sequenced messages for
+    fn announce_nonce(&mut self) {
+        #[cfg(feature = "auth")]
+        if self.key.is_some() {
+            // If this is lost, it's sent again once the peer's next frame is
+            // rejected
+            let _ = self.send(Ack::Nonce(self.nonce));
+        }
+    }
+
+    /// Whether `message` may only be sent or received wrapped in a
+    /// [`Sequenced`] message
+    fn must_be_sequenced(&self, message: &Message) -> bool {
+        #[cfg(feature = "auth")]
+        if self.key.is_some() {
+            return matches!(message, Message::Command(_));
+        }
+        let _ = message;
+        false
+    }
+
+    /// Oldest message the peer may still receive
+    fn base(&self) -> u8 {
+        match self.pending[self.failed..self.len].first() {
+            Some(oldest) => oldest.seq,
+            None => self.next_seq,
+        }
+    }
+
+    /// Sends the pending messages again, starting from the one at `from`
+    fn resend(&mut self, from: usize, now: u32) -> Result<(), Error<T::Error>> {
+        let base = self.base();
+        for i in from..self.len {
+            self.pending[i].base = base;
+            self.send(self.pending[i])?;
+        }
+        self.sent_at = now;
+        Ok(())
+    }
+
+    fn receive(&mut self, message: Message, now: u32) -> Result<Option<Event>, Error<T::Error>> {
+        if self.must_be_sequenced(&message) {
+            return Err(Error::Unsequenced);
+        }
+        match message {
+            Message::Sequenced(sequenced) => self.receive_sequenced(&sequenced),
+            Message::Ack(ack) => self.receive_ack(ack, now),
+            Message::Hello(_) => {
+                self.expected = None;
+                self.renew_nonce();
+                Ok(Some(Event::Received(message)))
+            }
+            message => Ok(Some(Event::Received(message))),
+        }
+    }
+
+    fn receive_sequenced(
+        &mut self,
+        sequenced: &Sequenced,
+    ) -> Result<Option<Event>, Error<T::Error>> {
+        // The sender gave up on anything before its base
+        let expected = match self.expected {
+            Some(expected) if !precedes(expected, sequenced.base) => expected,
+            _ => sequenced.base,
+        };
+
+        if sequenced.seq == expected {
+            // Only taken once acknowledged, otherwise the retransmission
+            // would be taken as a duplicate and never delivered
+            self.send(Ack::Ack(sequenced.seq))?;
+            let next = expected.wrapping_add(1);
+            self.expected = Some(next);
+            // Frames recorded over 128 messages ago would otherwise carry the
+            // sequence number expected once it wraps around
+            if next % 128 == 0 {
+                self.renew_nonce();
+            }
+            let message = sequenced.message().map_err(|e| Error::Decode(e.into()))?;
+            return Ok(Some(Event::Received(message)));
+        }
+
+        self.expected = Some(expected);
+        if precedes(sequenced.seq, expected) {
+            // Already delivered, the acknowledgement must have been lost
+            self.send(Ack::Ack(expected.wrapping_sub(1)))?;
+        } else {
+            self.send(Ack::Nack(expected))?;
+        }
+        Ok(None)
+    }
+
+    fn receive_ack(&mut self, ack: Ack, now: u32) -> Result<Option<Event>, Error<T::Error>> {
+        let seq = match ack {
+            Ack::Ack(seq) | Ack::Nack(seq) => seq,
+            Ack::Nonce(nonce) => return self.receive_nonce(nonce, now),
+        };
+        let Some(i) = self.pending[..self.len].iter().position(|s| s.seq == seq) else {
+            // Stale answer about a message that's no longer pending
+            return Ok(None);
+        };
+
+        match ack {
+            Ack::Ack(_) => {
+                self.pending[..self.len].rotate_left(i + 1);
+                self.len -= i + 1;
+                self.sent_at = now;
+                self.retries = 0;
+                Ok(Some(Event::Delivered(seq)))
+            }
+            _ => {
+                self.resend(i, now)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn receive_nonce(&mut self, nonce: u32, now: u32) -> Result<Option<Event>, Error<T::Error>> {
+        #[cfg(feature = "auth")]
+        if self.key.is_some() && self.peer_nonce != Some(nonce) {
+            self.peer_nonce = Some(nonce);
+            // Whatever is pending was tagged for another nonce
+            if self.len > self.failed {
+                self.resend(self.failed, now)?;
+            }
+        }
+        let _ = (nonce, now);
+        Ok(None)
+    }
+}
+
+/// Whether sequence number `a` comes before `b`
+fn precedes(a: u8, b: u8) -> bool {
+    let distance = b.wrapping_sub(a);
+    distance != 0 && distance <= 128
+}
@@ -0,0 +1,202 @@
+//! Sequence numbers and timestamps for readings
+//!
+//! A [`Sample`] wraps a reading together with a sequence number, that wraps
+//! around after `u16::MAX`, and the device tick count when the reading was
+//! taken, which also wraps around. The receiver feeds both into a
+//! [`SequenceTracker`] to detect lost or duplicated samples and measure the
+//! real sample rate.
+
+use core::mem::size_of;
+
+use crate::batch::Batch;
+use crate::frame;
+use crate::message;
+use crate::validate::{Validate, ValidationError};
+use crate::wire::WireMessage;
+
+/// Sequence number and timestamp
+pub const HEADER_SIZE: usize = size_of::<u16>() + size_of::<u32>();
+
+/// A reading stamped with its sequence number and device timestamp
+///
+/// The payload is the sequence number (`u16`), the timestamp (`u32`) and the
+/// encoded reading, for a [`SensorMessage`](crate::SensorMessage) its
+/// [`MessageKind`](crate::message::MessageKind) tag and payload. All values
+/// are little endian.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample<T> {
+    /// Wrapping sequence number, incremented once per sample sent
+    pub seq: u16,
+    /// Device tick count when the reading was taken
+    pub timestamp: u32,
+    pub value: T,
+}
+
+impl<T: WireMessage> WireMessage for Sample<T> {
+    const SIZE: usize = HEADER_SIZE + T::SIZE;
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        if buf.len() < HEADER_SIZE {
+            return Err(frame::Error::BufferTooSmall);
+        }
+        buf[0..2].copy_from_slice(&self.seq.to_le_bytes());
+        buf[2..6].copy_from_slice(&self.timestamp.to_le_bytes());
+        let len = self.value.encode_into(&mut buf[HEADER_SIZE..])?;
+        Ok(HEADER_SIZE + len)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(message::Error::InvalidLength);
+        }
+        Ok(Sample {
+            seq: u16::from_le_bytes([bytes[0], bytes[1]]),
+            timestamp: u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]),
+            value: T::decode(&bytes[HEADER_SIZE..])?,
+        })
+    }
+}
+
+impl<T: Validate> Validate for Sample<T> {
+    fn validate(&self) -> Result<(), ValidationError> {
+        self.value.validate()
+    }
+}
+
+/// Stamps readings with consecutive sequence numbers, for the sender
+#[derive(Clone, Debug, Default)]
+pub struct Sequencer {
+    next: u16,
+}
+
+impl Sequencer {
+    pub const fn new() -> Self {
+        Sequencer { next: 0 }
+    }
+
+    /// Wraps `value` in a sample with the next sequence number
+    pub fn stamp<T>(&mut self, timestamp: u32, value: T) -> Sample<T> {
+        let seq = self.next;
+        self.next = self.next.wrapping_add(1);
+        Sample {
+            seq,
+            timestamp,
+            value,
+        }
+    }
+
+    /// Numbers the readings of `batch` with the next sequence numbers
+    pub fn stamp_batch(&mut self, batch: &mut Batch) {
+        batch.seq = self.next;
+        self.next = self.next.wrapping_add(batch.len() as u16);
+    }
+}
+
+/// How a sample's sequence number relates to the previous one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SequenceStatus {
+    /// First sample seen by the tracker
+    First,
+    /// Sample follows the previous one
+    InOrder,
+    /// `lost` samples are missing before this one
+    Gap { lost: u16 },
+    /// Sample was already received, or arrived late
+    Duplicate,
+}
+
+/// Checks the sequence numbers of received samples, for the receiver
+///
+/// A sequence number up to half the range ahead of the last one is taken as
+/// a new sample, even if it wrapped around, anything else as a duplicate.
+#[derive(Clone, Debug, Default)]
+pub struct SequenceTracker {
+    last: Option<(u16, u32)>,
+    received: u32,
+    lost: u32,
+    duplicated: u32,
+    /// Sequence numbers and ticks elapsed between samples received in order
+    /// or after a gap, used to compute the rate
+    seq_span: u64,
+    tick_span: u64,
+}
+
+impl SequenceTracker {
+    pub const fn new() -> Self {
+        SequenceTracker {
+            last: None,
+            received: 0,
+            lost: 0,
+            duplicated: 0,
+            seq_span: 0,
+            tick_span: 0,
+        }
+    }
+
+    /// Records a received sample
+    pub fn update(&mut self, seq: u16, timestamp: u32) -> SequenceStatus {
+        self.received = self.received.wrapping_add(1);
+
+        let Some((last_seq, last_timestamp)) = self.last else {
+            self.last = Some((seq, timestamp));
+            return SequenceStatus::First;
+        };
+
+        let delta = seq.wrapping_sub(last_seq);
+        if delta == 0 || delta > u16::MAX / 2 {
+            self.duplicated = self.duplicated.wrapping_add(1);
+            return SequenceStatus::Duplicate;
+        }
+
+        self.last = Some((seq, timestamp));
+        self.seq_span += delta as u64;
+        self.tick_span += timestamp.wrapping_sub(last_timestamp) as u64;
+
+        if delta == 1 {
+            SequenceStatus::InOrder
+        } else {
+            let lost = delta - 1;
+            self.lost = self.lost.wrapping_add(lost as u32);
+            SequenceStatus::Gap { lost }
+        }
+    }
+
+    /// Forgets every sample received so far
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Samples received, including duplicates
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    /// Samples that never arrived
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+
+    /// Samples received more than once or out of order
+    pub fn duplicated(&self) -> u32 {
+        self.duplicated
+    }
+
+    /// Average device ticks between consecutive samples, lost samples
+    /// included
+    pub fn period_ticks(&self) -> Option<u32> {
+        if self.seq_span == 0 {
+            return None;
+        }
+        Some((self.tick_span / self.seq_span) as u32)
+    }
+
+    /// Average sample rate in mHz, given the frequency of the device ticks
+    pub fn rate_millihertz(&self, tick_hz: u32) -> Option<u32> {
+        if self.tick_span == 0 {
+            return None;
+        }
+        let rate = self.seq_span as u128 * tick_hz as u128 * 1000 / self.tick_span as u128;
+        Some(rate.min(u32::MAX as u128) as u32)
+    }
+}
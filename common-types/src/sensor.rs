@@ -0,0 +1,282 @@
+//! Readings from the ISM330DHCX sensor
+
+use core::mem::size_of;
+use core::ops::RangeInclusive;
+
+use crate::frame;
+use crate::message::{self, validated, write_payload, MessageKind};
+use crate::validate::{check_range, Validate, ValidationError};
+use crate::wire::{max_size, WireMessage};
+
+/// Operating temperature range of the sensor, in °C
+pub const TEMPERATURE_RANGE: RangeInclusive<f32> = -40.0..=85.0;
+/// Largest full scale of the accelerometer (±16 g), in m/s²
+pub const ACCELERATION_RANGE: RangeInclusive<f32> = -156.9064..=156.9064;
+/// Largest full scale of the gyroscope, in °/s
+pub const ANGULAR_RATE_RANGE: RangeInclusive<f32> = -4000.0..=4000.0;
+
+/// Temperature in °C
+#[derive(Clone, Copy, Debug, PartialEq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[wire(validate)]
+pub struct Temperature(pub f32);
+
+impl Temperature {
+    pub const KIND: MessageKind = MessageKind::Temperature;
+
+    pub fn to_bytes(self) -> [u8; size_of::<f32>()] {
+        self.0.to_le_bytes()
+    }
+
+    /// Writes the temperature as a framed packet into `buf`, returning the
+    /// number of bytes written
+    pub fn to_frame(self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        frame::encode(Self::KIND, &self.to_bytes(), buf)
+    }
+
+    /// Parses the payload of a frame holding a temperature
+    pub fn from_frame(frame: &frame::Frame) -> Result<Self, message::Error> {
+        if frame.kind != Self::KIND.tag() {
+            return Err(message::Error::UnknownKind(frame.kind));
+        }
+        Self::decode(frame.payload)
+    }
+}
+
+impl TryFrom<[u8; size_of::<f32>()]> for Temperature {
+    type Error = ValidationError;
+
+    fn try_from(bytes: [u8; size_of::<f32>()]) -> Result<Self, ValidationError> {
+        let temp = Temperature(f32::from_le_bytes(bytes));
+        temp.validate()?;
+        Ok(temp)
+    }
+}
+
+impl Validate for Temperature {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_range(self.0, TEMPERATURE_RANGE)
+    }
+}
+
+/// Temperature in hundredths of °C, for receivers without an FPU
+///
+/// Converting from [`Temperature`] rounds to the nearest hundredth, so a
+/// round trip is off by at most 0.005 °C, the same precision the display
+/// shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[wire(validate)]
+pub struct CentiCelsius(pub i16);
+
+impl CentiCelsius {
+    pub const KIND: MessageKind = MessageKind::TemperatureCenti;
+
+    /// Operating temperature range of the sensor
+    pub const MIN: CentiCelsius = CentiCelsius(-4000);
+    pub const MAX: CentiCelsius = CentiCelsius(8500);
+
+    pub fn to_bytes(self) -> [u8; size_of::<i16>()] {
+        self.0.to_le_bytes()
+    }
+
+    /// Whole degrees, rounded towards zero
+    pub fn degrees(self) -> i16 {
+        self.0 / 100
+    }
+
+    /// Hundredths of a degree, negative for negative temperatures
+    pub fn hundredths(self) -> i16 {
+        self.0 % 100
+    }
+}
+
+impl TryFrom<[u8; size_of::<i16>()]> for CentiCelsius {
+    type Error = ValidationError;
+
+    fn try_from(bytes: [u8; size_of::<i16>()]) -> Result<Self, ValidationError> {
+        let temp = CentiCelsius(i16::from_le_bytes(bytes));
+        temp.validate()?;
+        Ok(temp)
+    }
+}
+
+impl Validate for CentiCelsius {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if (CentiCelsius::MIN..=CentiCelsius::MAX).contains(self) {
+            Ok(())
+        } else {
+            Err(ValidationError::OutOfRange)
+        }
+    }
+}
+
+impl From<CentiCelsius> for Temperature {
+    fn from(temp: CentiCelsius) -> Self {
+        Temperature(temp.0 as f32 / 100.0)
+    }
+}
+
+impl From<Temperature> for CentiCelsius {
+    /// Rounds to the nearest hundredth, halves away from zero, saturating at
+    /// the limits of `i16`. NaN becomes 0 °C.
+    fn from(temp: Temperature) -> Self {
+        // `f32::round` isn't available in `core`
+        let offset = if temp.0 < 0.0 { -0.5 } else { 0.5 };
+        CentiCelsius((temp.0 * 100.0 + offset) as i16)
+    }
+}
+
+/// Size of a three axis reading: `x`, `y` and `z` as little endian `f32`
+const AXES_SIZE: usize = 3 * size_of::<f32>();
+
+fn axes_to_bytes(x: f32, y: f32, z: f32) -> [u8; AXES_SIZE] {
+    let mut bytes = [0; AXES_SIZE];
+    bytes[0..4].copy_from_slice(&x.to_le_bytes());
+    bytes[4..8].copy_from_slice(&y.to_le_bytes());
+    bytes[8..12].copy_from_slice(&z.to_le_bytes());
+    bytes
+}
+
+fn axes_from_bytes(bytes: [u8; AXES_SIZE]) -> (f32, f32, f32) {
+    let axis = |i: usize| f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    (axis(0), axis(4), axis(8))
+}
+
+/// Any reading the sensor provides
+///
+/// Each variant is sent with its own [`MessageKind`], the payloads are:
+///
+/// - `Temperature`: `f32` in °C.
+/// - `TemperatureCenti`: `i16` in hundredths of °C.
+/// - `Acceleration`: `x`, `y`, `z` as `f32` in m/s².
+/// - `AngularRate`: `x`, `y`, `z` as `f32` in °/s.
+///
+/// All values are little endian. Decoding fails for values outside of the
+/// sensor's range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SensorMessage {
+    Temperature(Temperature),
+    /// Same as `Temperature`, in fixed point
+    TemperatureCenti(CentiCelsius),
+    /// Linear acceleration in m/s²
+    Acceleration {
+        x: f32,
+        y: f32,
+        z: f32,
+    },
+    /// Angular rate in °/s
+    AngularRate {
+        x: f32,
+        y: f32,
+        z: f32,
+    },
+}
+
+impl SensorMessage {
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            SensorMessage::Temperature(_) => MessageKind::Temperature,
+            SensorMessage::TemperatureCenti(_) => MessageKind::TemperatureCenti,
+            SensorMessage::Acceleration { .. } => MessageKind::Acceleration,
+            SensorMessage::AngularRate { .. } => MessageKind::AngularRate,
+        }
+    }
+
+    /// Size of the payload of readings of the given kind, `None` if the kind
+    /// isn't a reading
+    pub fn payload_size(kind: MessageKind) -> Option<usize> {
+        match kind {
+            MessageKind::Temperature => Some(Temperature::SIZE),
+            MessageKind::TemperatureCenti => Some(CentiCelsius::SIZE),
+            MessageKind::Acceleration | MessageKind::AngularRate => Some(AXES_SIZE),
+            _ => None,
+        }
+    }
+
+    /// Writes the reading's payload into `buf`, returning the number of bytes
+    /// written
+    pub fn encode_payload(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        match *self {
+            SensorMessage::Temperature(temp) => temp.encode_into(buf),
+            SensorMessage::TemperatureCenti(temp) => temp.encode_into(buf),
+            SensorMessage::Acceleration { x, y, z } | SensorMessage::AngularRate { x, y, z } => {
+                write_payload(buf, &axes_to_bytes(x, y, z))
+            }
+        }
+    }
+
+    /// Parses the payload of a sensor reading of the given kind
+    pub fn from_payload(kind: MessageKind, payload: &[u8]) -> Result<Self, message::Error> {
+        match kind {
+            MessageKind::Temperature => {
+                Temperature::decode(payload).map(SensorMessage::Temperature)
+            }
+            MessageKind::TemperatureCenti => {
+                CentiCelsius::decode(payload).map(SensorMessage::TemperatureCenti)
+            }
+            MessageKind::Acceleration => payload
+                .try_into()
+                .map(axes_from_bytes)
+                .map(|(x, y, z)| SensorMessage::Acceleration { x, y, z })
+                .map_err(|_| message::Error::InvalidLength)
+                .and_then(validated),
+            MessageKind::AngularRate => payload
+                .try_into()
+                .map(axes_from_bytes)
+                .map(|(x, y, z)| SensorMessage::AngularRate { x, y, z })
+                .map_err(|_| message::Error::InvalidLength)
+                .and_then(validated),
+            _ => Err(message::Error::UnknownKind(kind.tag())),
+        }
+    }
+}
+
+/// Encoded as the [`MessageKind`] tag of the reading followed by its payload
+impl WireMessage for SensorMessage {
+    const SIZE: usize = 1 + max_size(&[Temperature::SIZE, CentiCelsius::SIZE, AXES_SIZE]);
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        let (tag, payload) = buf.split_first_mut().ok_or(frame::Error::BufferTooSmall)?;
+        *tag = self.kind().tag();
+        Ok(1 + self.encode_payload(payload)?)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        let (&tag, payload) = bytes.split_first().ok_or(message::Error::InvalidLength)?;
+        let kind = MessageKind::try_from(tag).map_err(message::Error::UnknownKind)?;
+        Self::from_payload(kind, payload)
+    }
+}
+
+impl Validate for SensorMessage {
+    fn validate(&self) -> Result<(), ValidationError> {
+        match *self {
+            SensorMessage::Temperature(temp) => temp.validate(),
+            SensorMessage::TemperatureCenti(temp) => temp.validate(),
+            SensorMessage::Acceleration { x, y, z } => {
+                check_range(x, ACCELERATION_RANGE)?;
+                check_range(y, ACCELERATION_RANGE)?;
+                check_range(z, ACCELERATION_RANGE)
+            }
+            SensorMessage::AngularRate { x, y, z } => {
+                check_range(x, ANGULAR_RATE_RANGE)?;
+                check_range(y, ANGULAR_RATE_RANGE)?;
+                check_range(z, ANGULAR_RATE_RANGE)
+            }
+        }
+    }
+}
+
+impl From<Temperature> for SensorMessage {
+    fn from(temp: Temperature) -> Self {
+        SensorMessage::Temperature(temp)
+    }
+}
+
+impl From<CentiCelsius> for SensorMessage {
+    fn from(temp: CentiCelsius) -> Self {
+        SensorMessage::TemperatureCenti(temp)
+    }
+}
@@ -0,0 +1,175 @@
+//! Summaries of readings over a window of time
+//!
+//! Instead of every reading, a device can send one [`Stats`] per channel
+//! every so often. An [`Accumulator`] per channel takes the readings as
+//! they're taken, without keeping them, and hands out the summary at the end
+//! of each window.
+
+use crate::sensor::SensorMessage;
+use crate::validate::{Validate, ValidationError};
+use crate::wire::WireMessage;
+
+/// A single value measured by the sensor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum Channel {
+    /// In °C
+    Temperature = 0,
+    /// In m/s²
+    AccelerationX = 1,
+    AccelerationY = 2,
+    AccelerationZ = 3,
+    /// In °/s
+    AngularRateX = 4,
+    AngularRateY = 5,
+    AngularRateZ = 6,
+}
+
+impl Channel {
+    /// Every channel
+    pub const ALL: [Channel; 7] = [
+        Channel::Temperature,
+        Channel::AccelerationX,
+        Channel::AccelerationY,
+        Channel::AccelerationZ,
+        Channel::AngularRateX,
+        Channel::AngularRateY,
+        Channel::AngularRateZ,
+    ];
+
+    /// Values a reading holds, along with their channels
+    pub fn values(reading: &SensorMessage) -> impl Iterator<Item = (Channel, f32)> {
+        let (channels, values): (&[Channel], [f32; 3]) = match *reading {
+            SensorMessage::Temperature(temp) => (&Channel::ALL[..1], [temp.0, 0.0, 0.0]),
+            SensorMessage::TemperatureCenti(temp) => {
+                (&Channel::ALL[..1], [temp.0 as f32 / 100.0, 0.0, 0.0])
+            }
+            SensorMessage::Acceleration { x, y, z } => (&Channel::ALL[1..4], [x, y, z]),
+            SensorMessage::AngularRate { x, y, z } => (&Channel::ALL[4..7], [x, y, z]),
+        };
+        channels.iter().copied().zip(values)
+    }
+}
+
+/// Summary of the readings of a channel over a window
+///
+/// The payload is the [`Channel`] (`u8`), the timestamp and the count
+/// (`u32`), then the minimum, maximum, mean and variance (`f32`), all little
+/// endian.
+#[derive(Clone, Copy, Debug, PartialEq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[wire(validate)]
+pub struct Stats {
+    pub channel: Channel,
+    /// Device tick count when the first reading was taken
+    pub timestamp: u32,
+    /// Number of readings summarized, never 0
+    pub count: u32,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    /// Population variance, in the channel's unit squared
+    pub variance: f32,
+}
+
+impl Validate for Stats {
+    fn validate(&self) -> Result<(), ValidationError> {
+        let values = [self.min, self.max, self.mean, self.variance];
+        if values.iter().any(|value| !value.is_finite()) {
+            Err(ValidationError::NotFinite)
+        } else if self.count == 0
+            || self.min > self.max
+            || !(self.min..=self.max).contains(&self.mean)
+            || self.variance < 0.0
+        {
+            Err(ValidationError::OutOfRange)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Running summary of the values of a channel
+///
+/// Uses Welford's algorithm, so that the variance doesn't lose precision when
+/// values are large compared to how much they vary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Accumulator {
+    channel: Channel,
+    timestamp: u32,
+    count: u32,
+    min: f32,
+    max: f32,
+    mean: f32,
+    /// Sum of the squared differences from the mean
+    m2: f32,
+}
+
+impl Accumulator {
+    pub const fn new(channel: Channel) -> Self {
+        Accumulator {
+            channel,
+            timestamp: 0,
+            count: 0,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Number of values since the window started
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Adds a value taken at device tick count `timestamp`, values that
+    /// aren't finite are ignored
+    pub fn push(&mut self, timestamp: u32, value: f32) {
+        if !value.is_finite() {
+            return;
+        }
+        if self.count == 0 {
+            self.timestamp = timestamp;
+        }
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Summary of the values so far, `None` if there's none
+    pub fn stats(&self) -> Option<Stats> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(Stats {
+            channel: self.channel,
+            timestamp: self.timestamp,
+            count: self.count,
+            min: self.min,
+            max: self.max,
+            // Rounding may leave the mean just outside of the range
+            mean: self.mean.clamp(self.min, self.max),
+            variance: (self.m2 / self.count as f32).max(0.0),
+        })
+    }
+
+    /// Summary of the values so far, starting a new window
+    pub fn take(&mut self) -> Option<Stats> {
+        let stats = self.stats();
+        *self = Accumulator::new(self.channel);
+        stats
+    }
+}
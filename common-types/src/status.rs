@@ -0,0 +1,106 @@
+//! Health reports sent periodically by the Nucleo
+//!
+//! Errors on the Nucleo are only logged over RTT, which the Raspberry Pi
+//! can't see. A [`DeviceStatus`] tells it how the Nucleo is doing: how long
+//! it's been running, why it last reset, what failed while setting it up and
+//! how many transfers have failed since.
+
+use crate::wire::WireMessage;
+
+/// Why a device last reset
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum ResetReason {
+    Unknown = 0,
+    PowerOn = 1,
+    /// Reset pin was pulled low, as the reset button does
+    Pin = 2,
+    /// Firmware asked for the reset
+    Software = 3,
+    Watchdog = 4,
+    /// Supply voltage dropped too low
+    Brownout = 5,
+}
+
+/// Set of faults a device is stuck with until it resets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Faults(pub u8);
+
+impl Faults {
+    pub const EMPTY: Faults = Faults(0);
+    /// Sensor didn't answer while being set up, no readings are sent
+    pub const SENSOR_INIT: Faults = Faults(1 << 0);
+    /// Display didn't answer while being set up
+    pub const DISPLAY_INIT: Faults = Faults(1 << 1);
+
+    pub const fn with(self, faults: Faults) -> Self {
+        Faults(self.0 | faults.0)
+    }
+
+    pub const fn contains(self, faults: Faults) -> bool {
+        self.0 & faults.0 == faults.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Health of a device
+///
+/// The payload is the uptime (`u32`), the [`ResetReason`] (`u8`), the
+/// [`Faults`] (`u8`) and the error counters (`u16` each), all little endian.
+/// Counters saturate instead of wrapping around.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceStatus {
+    /// Seconds since the device started
+    pub uptime: u32,
+    pub reset_reason: ResetReason,
+    pub faults: Faults,
+    /// Failed transfers on the sensor's I2C bus
+    pub sensor_errors: u16,
+    /// Display updates that failed even after retrying
+    pub display_errors: u16,
+    /// Display updates that had to be retried
+    pub display_retries: u16,
+    /// Messages that couldn't be written to the link
+    pub link_errors: u16,
+}
+
+impl DeviceStatus {
+    /// Status of a device that just started
+    pub const fn new(reset_reason: ResetReason) -> Self {
+        DeviceStatus {
+            uptime: 0,
+            reset_reason,
+            faults: Faults::EMPTY,
+            sensor_errors: 0,
+            display_errors: 0,
+            display_retries: 0,
+            link_errors: 0,
+        }
+    }
+
+    /// Whether something failed for good, or the device reset because it got
+    /// stuck or lost power
+    pub fn is_faulty(&self) -> bool {
+        !self.faults.is_empty()
+            || matches!(
+                self.reset_reason,
+                ResetReason::Watchdog | ResetReason::Brownout
+            )
+    }
+
+    /// Whether the device restarted or ran into new errors since it reported
+    /// `previous`
+    pub fn is_worse_than(&self, previous: &DeviceStatus) -> bool {
+        self.uptime < previous.uptime
+            || self.sensor_errors > previous.sensor_errors
+            || self.display_errors > previous.display_errors
+            || self.display_retries > previous.display_retries
+            || self.link_errors > previous.link_errors
+    }
+}
@@ -0,0 +1,157 @@
+//! Mapping the peer's timestamps onto the local clock
+//!
+//! Each device timestamps things with its own tick counter, which starts at
+//! a different time, runs at a different rate and drifts. To relate them, one
+//! end sends a [`TimeRequest`] stamped with its own tick count and the peer
+//! answers right away with a [`TimeReply`] carrying the tick counts when the
+//! request arrived and when the answer left. Assuming both directions take as
+//! long, the middle of the exchange happened at the same instant on both
+//! clocks, which [`ClockSync`] uses as the reference to map remote ticks to
+//! local ones. Consecutive exchanges tell how fast the remote clock really
+//! runs.
+//!
+//! Exchanges have to happen more often than it takes either counter to cover
+//! half its range, so that differences between tick counts stay unambiguous.
+
+use crate::validate::{Validate, ValidationError};
+use crate::wire::WireMessage;
+
+/// Weight of the latest exchange in the rate estimate is 1 / `SMOOTHING`
+const SMOOTHING: u64 = 8;
+
+/// Asks the peer for its time
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeRequest {
+    /// Requester's tick count when the request was sent
+    pub origin: u32,
+}
+
+/// Answer to a [`TimeRequest`]
+///
+/// The payload is the four fields as little endian `u32`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[wire(validate)]
+pub struct TimeReply {
+    /// `origin` of the request being answered, in the requester's ticks
+    pub origin: u32,
+    /// Replier's tick count when the request arrived
+    pub receive: u32,
+    /// Replier's tick count when the reply was sent
+    pub transmit: u32,
+    /// Nominal frequency of the replier's ticks, in hertz
+    pub tick_hz: u32,
+}
+
+impl TimeReply {
+    pub fn new(request: &TimeRequest, receive: u32, transmit: u32, tick_hz: u32) -> Self {
+        TimeReply {
+            origin: request.origin,
+            receive,
+            transmit,
+            tick_hz,
+        }
+    }
+}
+
+impl Validate for TimeReply {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.tick_hz == 0 {
+            Err(ValidationError::OutOfRange)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Estimate of how the peer's clock relates to the local one
+pub struct ClockSync {
+    local_hz: u32,
+    /// Replies taking longer than this many local ticks are ignored
+    max_round_trip: u32,
+    /// Nominal frequency of the remote ticks, 0 until a reply arrives
+    remote_hz: u32,
+    /// Local ticks per remote tick, nominal and measured, as 32.32 fixed point
+    nominal_rate: u64,
+    rate: u64,
+    /// A remote tick count and the local one it maps to
+    reference: Option<(u32, u32)>,
+}
+
+impl ClockSync {
+    /// Syncs with a peer from a clock ticking at `local_hz`, ignoring
+    /// exchanges slower than `max_round_trip` local ticks
+    pub const fn new(local_hz: u32, max_round_trip: u32) -> Self {
+        ClockSync {
+            local_hz,
+            max_round_trip,
+            remote_hz: 0,
+            nominal_rate: 0,
+            rate: 0,
+            reference: None,
+        }
+    }
+
+    /// Request to send at local tick count `now`
+    pub fn request(&self, now: u32) -> TimeRequest {
+        TimeRequest { origin: now }
+    }
+
+    /// Takes a reply received at local tick count `now` into account,
+    /// returning the round trip time in local ticks, or `None` if it took
+    /// too long to be accurate
+    pub fn update(&mut self, reply: &TimeReply, now: u32) -> Option<u32> {
+        let elapsed = now.wrapping_sub(reply.origin);
+        if elapsed > self.max_round_trip {
+            return None;
+        }
+
+        // A different peer, or one with a different clock, starts over
+        if reply.tick_hz != self.remote_hz {
+            self.remote_hz = reply.tick_hz;
+            self.nominal_rate = ((self.local_hz as u64) << 32) / reply.tick_hz as u64;
+            self.rate = self.nominal_rate;
+            self.reference = None;
+        }
+
+        let processing = reply.transmit.wrapping_sub(reply.receive);
+        let remote = reply.receive.wrapping_add(processing / 2);
+        let local = reply.origin.wrapping_add(elapsed / 2);
+
+        if let Some((last_remote, last_local)) = self.reference {
+            let remote_span = remote.wrapping_sub(last_remote);
+            if remote_span != 0 && remote_span <= i32::MAX as u32 {
+                let local_span = local.wrapping_sub(last_local) as u64;
+                let measured = (local_span << 32) / remote_span as u64;
+                self.rate = self.rate - self.rate / SMOOTHING + measured / SMOOTHING;
+            }
+        }
+        self.reference = Some((remote, local));
+
+        Some(elapsed.saturating_sub(self.scale(processing as i32 as i64)))
+    }
+
+    /// Local tick count at which the remote clock read `remote`, `None`
+    /// until a reply has been received
+    pub fn to_local(&self, remote: u32) -> Option<u32> {
+        let (reference_remote, reference_local) = self.reference?;
+        // Timestamps from before the last exchange are common, readings are
+        // sent after they're taken
+        let elapsed = remote.wrapping_sub(reference_remote) as i32;
+        Some(reference_local.wrapping_add(self.scale(elapsed as i64)))
+    }
+
+    /// How much faster the remote clock runs than its nominal frequency, in
+    /// parts per million of the local clock
+    pub fn drift_ppm(&self) -> Option<i32> {
+        self.reference?;
+        let difference = self.nominal_rate as i128 - self.rate as i128;
+        Some((difference * 1_000_000 / self.rate as i128) as i32)
+    }
+
+    /// Converts remote ticks to local ticks, modulo 2^32
+    fn scale(&self, remote_ticks: i64) -> u32 {
+        ((remote_ticks as i128 * self.rate as i128) >> 32) as u32
+    }
+}
@@ -0,0 +1,114 @@
+//! Temperatures tagged with their unit
+//!
+//! Converting between the wrappers is explicit, through `From`, so display
+//! and logging code can't mix up units. Only values in the same unit can be
+//! compared, the conversions round, so comparing across units would depend on
+//! which side gets converted.
+
+use core::fmt;
+
+use crate::sensor::{CentiCelsius, Temperature};
+
+/// Temperature in degrees Celsius
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Celsius(pub f32);
+
+/// Temperature in degrees Fahrenheit
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fahrenheit(pub f32);
+
+/// Temperature in kelvin
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Kelvin(pub f32);
+
+/// Offset between the Celsius and Kelvin scales
+const ZERO_CELSIUS_IN_KELVIN: f32 = 273.15;
+
+impl From<Fahrenheit> for Celsius {
+    fn from(f: Fahrenheit) -> Self {
+        Celsius((f.0 - 32.0) * 5.0 / 9.0)
+    }
+}
+
+impl From<Kelvin> for Celsius {
+    fn from(k: Kelvin) -> Self {
+        Celsius(k.0 - ZERO_CELSIUS_IN_KELVIN)
+    }
+}
+
+impl From<Celsius> for Fahrenheit {
+    fn from(c: Celsius) -> Self {
+        Fahrenheit(c.0 * 9.0 / 5.0 + 32.0)
+    }
+}
+
+impl From<Kelvin> for Fahrenheit {
+    fn from(k: Kelvin) -> Self {
+        Fahrenheit::from(Celsius::from(k))
+    }
+}
+
+impl From<Celsius> for Kelvin {
+    fn from(c: Celsius) -> Self {
+        Kelvin(c.0 + ZERO_CELSIUS_IN_KELVIN)
+    }
+}
+
+impl From<Fahrenheit> for Kelvin {
+    fn from(f: Fahrenheit) -> Self {
+        Kelvin::from(Celsius::from(f))
+    }
+}
+
+impl From<Temperature> for Celsius {
+    fn from(temp: Temperature) -> Self {
+        Celsius(temp.0)
+    }
+}
+
+impl From<Celsius> for Temperature {
+    fn from(c: Celsius) -> Self {
+        Temperature(c.0)
+    }
+}
+
+impl From<CentiCelsius> for Celsius {
+    fn from(temp: CentiCelsius) -> Self {
+        Celsius::from(Temperature::from(temp))
+    }
+}
+
+/// Implements formatting
+macro_rules! unit {
+    ($unit:ident, $symbol:literal) => {
+        /// Formats the value followed by the unit, honoring the precision
+        impl fmt::Display for $unit {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match f.precision() {
+                    Some(precision) => write!(f, "{:.*} {}", precision, self.0, $symbol),
+                    None => write!(f, "{} {}", self.0, $symbol),
+                }
+            }
+        }
+
+        #[cfg(feature = "defmt")]
+        impl defmt::Format for $unit {
+            fn format(&self, f: defmt::Formatter) {
+                defmt::write!(f, "{=f32} {=str}", self.0, $symbol)
+            }
+        }
+    };
+}
+
+unit!(Celsius, "°C");
+unit!(Fahrenheit, "°F");
+unit!(Kelvin, "K");
+
+impl Temperature {
+    pub fn celsius(self) -> Celsius {
+        Celsius::from(self)
+    }
+}
@@ -0,0 +1,496 @@
+//! Firmware updates over the link
+//!
+//! The [`Sender`] holds the whole image and pushes it to the device being
+//! updated, whose [`Receiver`] writes it to a [`Storage`]:
+//!
+//! 1. [`UpdateBegin`] announces the image's size and SHA-256 hash. A
+//!    receiver already holding part of that same image answers with how much
+//!    of it it has, so an interrupted update resumes where it stopped.
+//! 2. [`UpdateChunk`]s carry the image [`CHUNK_SIZE`] bytes at a time, in
+//!    order, each numbered and with a CRC of its own.
+//! 3. [`UpdateCommit`] asks the receiver to check the whole image against its
+//!    hash and boot it from then on.
+//!
+//! The CRCs only catch line noise, the hash is what makes sure the image
+//! booted is byte for byte the one that was sent.
+//!
+//! The receiver answers every message with an [`UpdateReply`] telling the
+//! offset it expects next, which is all the sender needs to carry on. One
+//! message is sent at a time: if no reply arrives, the sender sends the same
+//! message again, so the receiver answers a repeated commit of the image it
+//! committed last with [`UpdateStatus::Committed`] again.
+
+use sha2::{Digest, Sha256};
+
+use crate::crc::crc16;
+use crate::frame;
+use crate::message::{self, Message};
+use crate::validate::{Validate, ValidationError};
+use crate::wire::WireMessage;
+
+/// Bytes of the image carried by every chunk but the last
+pub const CHUNK_SIZE: usize = 64;
+
+/// Largest image that can be sent, as many chunks as there are sequence
+/// numbers
+pub const MAX_IMAGE_SIZE: usize = CHUNK_SIZE << 16;
+
+/// SHA-256 hash of an image
+pub type Hash = [u8; 32];
+
+/// Hash of `image`, as announced by the sender
+pub fn hash(image: &[u8]) -> Hash {
+    Sha256::digest(image).into()
+}
+
+/// Starts sending an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[wire(validate)]
+pub struct UpdateBegin {
+    /// Size of the image in bytes
+    pub size: u32,
+    /// Hash of the whole image
+    pub hash: Hash,
+}
+
+impl Validate for UpdateBegin {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.size == 0 || self.size as usize > MAX_IMAGE_SIZE {
+            Err(ValidationError::OutOfRange)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Part of an image
+///
+/// The payload is the sequence number (`u16`), the CRC-16 of the data
+/// (`u16`), both little endian, and up to [`CHUNK_SIZE`] bytes of data. The
+/// chunk with sequence number `seq` starts `seq * CHUNK_SIZE` bytes into the
+/// image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UpdateChunk {
+    pub seq: u16,
+    /// CRC-16 of the data, as computed by the sender
+    pub crc: u16,
+    len: usize,
+    data: [u8; CHUNK_SIZE],
+}
+
+impl UpdateChunk {
+    /// Chunk carrying `data`, `None` if it's empty or longer than
+    /// [`CHUNK_SIZE`]
+    pub fn new(seq: u16, data: &[u8]) -> Option<Self> {
+        let mut chunk = UpdateChunk {
+            seq,
+            crc: crc16(data),
+            len: data.len(),
+            data: [0; CHUNK_SIZE],
+        };
+        chunk.data.get_mut(..data.len())?.copy_from_slice(data);
+        if data.is_empty() {
+            None
+        } else {
+            Some(chunk)
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    /// Offset of the chunk in the image
+    pub fn offset(&self) -> u32 {
+        self.seq as u32 * CHUNK_SIZE as u32
+    }
+
+    /// Whether the data still matches its CRC
+    pub fn is_intact(&self) -> bool {
+        crc16(self.data()) == self.crc
+    }
+}
+
+impl WireMessage for UpdateChunk {
+    const SIZE: usize = 4 + CHUNK_SIZE;
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        let size = 4 + self.len;
+        let buf = buf.get_mut(..size).ok_or(frame::Error::BufferTooSmall)?;
+        buf[0..2].copy_from_slice(&self.seq.to_le_bytes());
+        buf[2..4].copy_from_slice(&self.crc.to_le_bytes());
+        buf[4..].copy_from_slice(self.data());
+        Ok(size)
+    }
+
+    /// Doesn't check the CRC, so that the receiver can tell the sender
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        let [s0, s1, c0, c1, ref data @ ..] = *bytes else {
+            return Err(message::Error::InvalidLength);
+        };
+        let mut chunk = UpdateChunk::new(u16::from_le_bytes([s0, s1]), data)
+            .ok_or(message::Error::InvalidLength)?;
+        chunk.crc = u16::from_le_bytes([c0, c1]);
+        Ok(chunk)
+    }
+}
+
+/// Serialized as its sequence number, its CRC and its data as bytes
+#[cfg(feature = "serde")]
+impl serde::Serialize for UpdateChunk {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        struct Data<'a>(&'a [u8]);
+
+        impl serde::Serialize for Data<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        let mut state = serializer.serialize_struct("UpdateChunk", 3)?;
+        state.serialize_field("seq", &self.seq)?;
+        state.serialize_field("crc", &self.crc)?;
+        state.serialize_field("data", &Data(self.data()))?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for UpdateChunk {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use core::fmt;
+        use serde::de::{self, SeqAccess, Visitor};
+
+        /// Data copied into a chunk's buffer as it's deserialized
+        struct Data(usize, [u8; CHUNK_SIZE]);
+
+        impl<'de> serde::Deserialize<'de> for Data {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct DataVisitor;
+
+                impl<'de> Visitor<'de> for DataVisitor {
+                    type Value = Data;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "1 to {} bytes", CHUNK_SIZE)
+                    }
+
+                    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Data, E> {
+                        let mut data = Data(bytes.len(), [0; CHUNK_SIZE]);
+                        data.1
+                            .get_mut(..bytes.len())
+                            .ok_or_else(|| E::invalid_length(bytes.len(), &self))?
+                            .copy_from_slice(bytes);
+                        Ok(data)
+                    }
+
+                    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Data, A::Error> {
+                        let mut data = Data(0, [0; CHUNK_SIZE]);
+                        while let Some(byte) = seq.next_element()? {
+                            if data.0 == CHUNK_SIZE {
+                                return Err(de::Error::invalid_length(data.0 + 1, &self));
+                            }
+                            data.1[data.0] = byte;
+                            data.0 += 1;
+                        }
+                        Ok(data)
+                    }
+                }
+
+                deserializer.deserialize_bytes(DataVisitor)
+            }
+        }
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "UpdateChunk")]
+        struct Raw {
+            seq: u16,
+            crc: u16,
+            data: Data,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        let mut chunk = UpdateChunk::new(raw.seq, &raw.data.1[..raw.data.0])
+            .ok_or_else(|| de::Error::custom("empty chunk"))?;
+        chunk.crc = raw.crc;
+        Ok(chunk)
+    }
+}
+
+/// Asks the receiver to boot the image it received
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdateCommit {
+    /// Hash of the whole image, as announced when it began
+    pub hash: Hash,
+}
+
+/// How an update is going, from the receiver's side
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum UpdateStatus {
+    /// Waiting for the chunk at the offset, or for the commit once the whole
+    /// image arrived
+    Ready = 0,
+    /// Chunk didn't match its CRC and has to be sent again
+    Corrupt = 1,
+    /// Image was checked and will be booted
+    Committed = 2,
+    /// Image doesn't fit in the receiver's storage
+    TooLarge = 3,
+    /// Whole image doesn't match its hash, the update has to start over
+    Mismatch = 4,
+    /// No update was begun, or the receiver restarted since
+    Idle = 5,
+    /// Receiver failed to store the image
+    StorageFailed = 6,
+    /// Receiver can't be updated over the link
+    Unsupported = 7,
+}
+
+/// Answer to every update message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, WireMessage)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdateReply {
+    pub status: UpdateStatus,
+    /// Bytes of the image received so far, where the next chunk starts
+    pub offset: u32,
+}
+
+impl UpdateReply {
+    pub const fn new(status: UpdateStatus, offset: u32) -> Self {
+        UpdateReply { status, offset }
+    }
+}
+
+/// Where the receiver keeps the image
+pub trait Storage {
+    type Error;
+
+    /// Largest image that can be stored, in bytes
+    fn capacity(&self) -> u32;
+
+    /// Prepares to store a new image of `size` bytes, dropping any partial one
+    fn begin(&mut self, size: u32) -> Result<(), Self::Error>;
+
+    /// Stores `data` at `offset` into the image, offsets only go forward
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Makes the complete image the one booted from now on
+    fn commit(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Image being received
+struct Transfer {
+    size: u32,
+    hash: Hash,
+    received: u32,
+    /// Hash of the bytes received so far
+    running: Sha256,
+}
+
+/// Device side of an update, answering the [`Sender`]
+pub struct Receiver<S> {
+    storage: S,
+    transfer: Option<Transfer>,
+    /// Size and hash of the image committed last
+    committed: Option<(u32, Hash)>,
+}
+
+impl<S: Storage> Receiver<S> {
+    pub const fn new(storage: S) -> Self {
+        Receiver {
+            storage,
+            transfer: None,
+            committed: None,
+        }
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    /// Handles an update message, returning the reply to send, or `None` if
+    /// it isn't an update message
+    pub fn handle(&mut self, message: &Message) -> Option<UpdateReply> {
+        match message {
+            Message::UpdateBegin(begin) => Some(self.begin(begin)),
+            Message::UpdateChunk(chunk) => Some(self.chunk(chunk)),
+            Message::UpdateCommit(commit) => Some(self.commit(commit)),
+            _ => None,
+        }
+    }
+
+    fn begin(&mut self, begin: &UpdateBegin) -> UpdateReply {
+        match &self.transfer {
+            // Same image again, carry on where it stopped
+            Some(transfer) if transfer.size == begin.size && transfer.hash == begin.hash => {
+                return UpdateReply::new(UpdateStatus::Ready, transfer.received);
+            }
+            _ => self.transfer = None,
+        }
+        if begin.size > self.storage.capacity() {
+            return UpdateReply::new(UpdateStatus::TooLarge, 0);
+        }
+        if self.storage.begin(begin.size).is_err() {
+            return UpdateReply::new(UpdateStatus::StorageFailed, 0);
+        }
+        self.committed = None;
+        self.transfer = Some(Transfer {
+            size: begin.size,
+            hash: begin.hash,
+            received: 0,
+            running: Sha256::new(),
+        });
+        UpdateReply::new(UpdateStatus::Ready, 0)
+    }
+
+    fn chunk(&mut self, chunk: &UpdateChunk) -> UpdateReply {
+        let Some(transfer) = &mut self.transfer else {
+            return UpdateReply::new(UpdateStatus::Idle, 0);
+        };
+        let received = transfer.received;
+        // A duplicate or a chunk from too far ahead, ask for the right one
+        if chunk.offset() != received {
+            return UpdateReply::new(UpdateStatus::Ready, received);
+        }
+        let expected = (transfer.size - received).min(CHUNK_SIZE as u32);
+        if !chunk.is_intact() || chunk.data().len() as u32 != expected {
+            return UpdateReply::new(UpdateStatus::Corrupt, received);
+        }
+        if self.storage.write(received, chunk.data()).is_err() {
+            return UpdateReply::new(UpdateStatus::StorageFailed, received);
+        }
+        transfer.running.update(chunk.data());
+        transfer.received += expected;
+        UpdateReply::new(UpdateStatus::Ready, transfer.received)
+    }
+
+    fn commit(&mut self, commit: &UpdateCommit) -> UpdateReply {
+        let Some(transfer) = &self.transfer else {
+            // The reply to the commit was lost, the sender is asking again
+            return match self.committed {
+                Some((size, hash)) if hash == commit.hash => {
+                    UpdateReply::new(UpdateStatus::Committed, size)
+                }
+                _ => UpdateReply::new(UpdateStatus::Idle, 0),
+            };
+        };
+        let received = transfer.received;
+        if received < transfer.size {
+            return UpdateReply::new(UpdateStatus::Ready, received);
+        }
+        let hash: Hash = transfer.running.clone().finalize().into();
+        if commit.hash != transfer.hash || hash != transfer.hash {
+            self.transfer = None;
+            return UpdateReply::new(UpdateStatus::Mismatch, 0);
+        }
+        if self.storage.commit().is_err() {
+            return UpdateReply::new(UpdateStatus::StorageFailed, received);
+        }
+        self.transfer = None;
+        self.committed = Some((received, hash));
+        UpdateReply::new(UpdateStatus::Committed, received)
+    }
+}
+
+/// Error sending an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Image is empty or larger than [`MAX_IMAGE_SIZE`]
+    InvalidSize,
+    /// Receiver gave up on the update
+    Refused(UpdateStatus),
+    /// Receiver asked for an offset that isn't the start of a chunk
+    BadOffset(u32),
+}
+
+/// Host side of an update, pushing an image to the [`Receiver`]
+pub struct Sender<'a> {
+    image: &'a [u8],
+    hash: Hash,
+    /// Message sent last, sent again if no reply arrives
+    current: Message,
+    done: bool,
+}
+
+impl<'a> Sender<'a> {
+    pub fn new(image: &'a [u8]) -> Result<Self, Error> {
+        if image.is_empty() || image.len() > MAX_IMAGE_SIZE {
+            return Err(Error::InvalidSize);
+        }
+        let hash = hash(image);
+        let begin = UpdateBegin {
+            size: image.len() as u32,
+            hash,
+        };
+        Ok(Sender {
+            image,
+            hash,
+            current: begin.into(),
+            done: false,
+        })
+    }
+
+    /// Message to send, first the [`UpdateBegin`], and again whenever a reply
+    /// doesn't arrive in time
+    pub fn message(&self) -> Message {
+        self.current
+    }
+
+    /// Whether the receiver committed the image
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Bytes of the image the receiver has so far
+    pub fn progress(&self) -> u32 {
+        match self.current {
+            Message::UpdateChunk(chunk) => chunk.offset(),
+            Message::UpdateCommit(_) => self.image.len() as u32,
+            _ => 0,
+        }
+    }
+
+    /// Takes the receiver's reply into account, returning the message to send
+    /// next, or `None` once the image is committed
+    pub fn handle(&mut self, reply: &UpdateReply) -> Result<Option<Message>, Error> {
+        let next: Message = match reply.status {
+            UpdateStatus::Ready | UpdateStatus::Corrupt => {
+                let offset = reply.offset as usize;
+                if offset == self.image.len() {
+                    UpdateCommit { hash: self.hash }.into()
+                } else if offset > self.image.len() || !offset.is_multiple_of(CHUNK_SIZE) {
+                    return Err(Error::BadOffset(reply.offset));
+                } else {
+                    let end = self.image.len().min(offset + CHUNK_SIZE);
+                    let seq = (offset / CHUNK_SIZE) as u16;
+                    UpdateChunk::new(seq, &self.image[offset..end])
+                        .expect("Chunks are never empty")
+                        .into()
+                }
+            }
+            UpdateStatus::Committed => {
+                self.done = true;
+                return Ok(None);
+            }
+            // The receiver lost track of the update, start it over
+            UpdateStatus::Idle => UpdateBegin {
+                size: self.image.len() as u32,
+                hash: self.hash,
+            }
+            .into(),
+            status => return Err(Error::Refused(status)),
+        };
+        self.current = next;
+        Ok(Some(next))
+    }
+}
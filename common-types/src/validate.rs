@@ -0,0 +1,34 @@
+//! Validation of decoded values
+//!
+//! Every message checks what it decodes, so a corrupted frame that happens to
+//! pass the CRC, or a misbehaving sender, can't hand the receiver NaN or a
+//! reading the sensor could never produce.
+
+use core::ops::RangeInclusive;
+
+/// Reason a decoded value was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// Value is NaN or infinite
+    NotFinite,
+    /// Value is outside of what the sensor or device can produce
+    OutOfRange,
+    /// Value doesn't name any variant of an enumeration
+    UnknownVariant,
+}
+
+/// Checks that a decoded value makes sense
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationError>;
+}
+
+/// Checks that `value` is finite and within `range`
+pub fn check_range(value: f32, range: RangeInclusive<f32>) -> Result<(), ValidationError> {
+    if !value.is_finite() {
+        Err(ValidationError::NotFinite)
+    } else if !range.contains(&value) {
+        Err(ValidationError::OutOfRange)
+    } else {
+        Ok(())
+    }
+}
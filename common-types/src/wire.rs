@@ -0,0 +1,154 @@
+//! Byte encoding shared by every message
+//!
+//! Each message implements [`WireMessage`], so code moving messages around
+//! can size its buffers and encode or decode them without knowing which
+//! message it's handling. New messages should use
+//! [`#[derive(WireMessage)]`](macro@WireMessage) rather than packing bytes by
+//! hand:
+//!
+//! ```
+//! use common_types::WireMessage;
+//!
+//! #[derive(Debug, PartialEq, WireMessage)]
+//! struct Pressure {
+//!     pascals: u32,
+//!     sensor: u8,
+//! }
+//!
+//! let mut buf = [0; Pressure::SIZE];
+//! let len = Pressure { pascals: 101_325, sensor: 2 }.encode_into(&mut buf).unwrap();
+//! assert_eq!(&buf[..len], &[0xCD, 0x8B, 0x01, 0x00, 0x02]);
+//! ```
+
+use core::mem::size_of;
+
+use crate::frame;
+use crate::message::{self, write_payload};
+use crate::validate::ValidationError;
+
+pub use common_types_derive::WireMessage;
+
+/// A value with a fixed little endian byte encoding
+pub trait WireMessage: Sized {
+    /// Largest number of bytes the encoded value takes, the exact size for
+    /// values that always take the same space
+    const SIZE: usize;
+
+    /// Writes the encoded value into `buf`, returning the number of bytes
+    /// written
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error>;
+
+    /// Parses an encoded value, rejecting values the message doesn't allow
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error>;
+}
+
+/// Largest of `sizes`, for the [`WireMessage::SIZE`] of enums
+#[doc(hidden)]
+pub const fn max_size(sizes: &[usize]) -> usize {
+    let mut max = 0;
+    let mut i = 0;
+    while i < sizes.len() {
+        if sizes[i] > max {
+            max = sizes[i];
+        }
+        i += 1;
+    }
+    max
+}
+
+macro_rules! primitive {
+    ($($ty:ty),*) => {$(
+        impl WireMessage for $ty {
+            const SIZE: usize = size_of::<$ty>();
+
+            fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+                write_payload(buf, &self.to_le_bytes())
+            }
+
+            fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+                bytes
+                    .try_into()
+                    .map(<$ty>::from_le_bytes)
+                    .map_err(|_| message::Error::InvalidLength)
+            }
+        }
+    )*};
+}
+
+primitive!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+/// Encoded as a `u8`, `0` or `1`
+impl WireMessage for bool {
+    const SIZE: usize = 1;
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        write_payload(buf, &[*self as u8])
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        match *bytes {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            [_] => Err(ValidationError::UnknownVariant.into()),
+            _ => Err(message::Error::InvalidLength),
+        }
+    }
+}
+
+/// Encoded as the bytes themselves
+impl<const N: usize> WireMessage for [u8; N] {
+    const SIZE: usize = N;
+
+    fn encode_into(&self, buf: &mut [u8]) -> Result<usize, frame::Error> {
+        write_payload(buf, self)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, message::Error> {
+        bytes.try_into().map_err(|_| message::Error::InvalidLength)
+    }
+}
+
+/// Encodes a field at `offset`, used by the derive macro
+#[doc(hidden)]
+pub fn encode_field<T: WireMessage>(
+    value: &T,
+    buf: &mut [u8],
+    offset: &mut usize,
+) -> Result<(), frame::Error> {
+    let buf = buf.get_mut(*offset..).ok_or(frame::Error::BufferTooSmall)?;
+    *offset += value.encode_into(buf)?;
+    Ok(())
+}
+
+/// Decodes a field taking exactly its size at `offset`, used by the derive
+/// macro
+#[doc(hidden)]
+pub fn decode_field<T: WireMessage>(bytes: &[u8], offset: &mut usize) -> Result<T, message::Error> {
+    let field = bytes
+        .get(*offset..*offset + T::SIZE)
+        .ok_or(message::Error::InvalidLength)?;
+    *offset += T::SIZE;
+    T::decode(field)
+}
+
+/// Decodes a field from every byte left after `offset`, used by the derive
+/// macro
+#[doc(hidden)]
+pub fn decode_last_field<T: WireMessage>(
+    bytes: &[u8],
+    offset: &mut usize,
+) -> Result<T, message::Error> {
+    let field = bytes.get(*offset..).ok_or(message::Error::InvalidLength)?;
+    *offset = bytes.len();
+    T::decode(field)
+}
+
+/// Checks that nothing is left after `offset`, used by the derive macro
+#[doc(hidden)]
+pub fn decode_end(bytes: &[u8], offset: usize) -> Result<(), message::Error> {
+    if bytes.len() == offset {
+        Ok(())
+    } else {
+        Err(message::Error::InvalidLength)
+    }
+}
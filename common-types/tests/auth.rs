@@ -0,0 +1,238 @@
+#![cfg(feature = "auth")]
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use common_types::auth::{Key, MAX_FRAME_SIZE, TAG_SIZE};
+use common_types::command::Command;
+use common_types::crc::crc16;
+use common_types::frame::{DecodeError, Decoder};
+use common_types::hello::{Hello, Version};
+use common_types::message::Message;
+use common_types::reliable::{Error, Event, Link, Transport};
+use common_types::{SensorMessage, Temperature};
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// Test cases 1, 2, 5 and 6 of RFC 4231, tags are the HMAC's first bytes
+#[test]
+fn rfc4231_vectors() {
+    let cases: [(Vec<u8>, &[u8], &str); 4] = [
+        (
+            vec![0x0B; 20],
+            b"Hi There",
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+        ),
+        (
+            b"Jefe".to_vec(),
+            b"what do ya want for nothing?",
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+        ),
+        (
+            vec![0x0C; 20],
+            b"Test With Truncation",
+            "a3b6167473100ee06e0c796c2955552b",
+        ),
+        // Keys longer than a block are hashed first
+        (
+            vec![0xAA; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+        ),
+    ];
+
+    for (key, data, mac) in cases {
+        let key = Key::new(&key);
+        let tag = key.tag(data);
+        assert_eq!(tag[..], hex(mac)[..TAG_SIZE]);
+        assert!(key.verify(data, &tag));
+        assert!(!key.verify(b"something else", &tag));
+    }
+}
+
+fn decode(decoder: &mut Decoder, bytes: &[u8]) -> Vec<Result<Message, DecodeError>> {
+    bytes
+        .iter()
+        .filter_map(|&byte| decoder.push(byte).transpose())
+        .collect()
+}
+
+#[test]
+fn rejects_frames_without_a_valid_tag() {
+    let key = Key::new(b"bench key");
+    let command = Message::Command(Command::SetBrightness(3));
+    let mut buf = [0; MAX_FRAME_SIZE];
+    let len = key.encode(&command, &mut buf).unwrap();
+    let frame = &buf[..len];
+
+    let mut decoder = Decoder::with_key(key.clone());
+    assert_eq!(decode(&mut decoder, frame), [Ok(command)]);
+
+    // Plain frames, frames tagged with another key and tampered frames whose
+    // CRC was fixed up are all rejected
+    let mut plain = [0; MAX_FRAME_SIZE];
+    let plain_len = command.to_frame(&mut plain).unwrap();
+    let mut forged = [0; MAX_FRAME_SIZE];
+    Key::new(b"guess").encode(&command, &mut forged).unwrap();
+    let mut tampered = frame.to_vec();
+    tampered[5] = 7;
+    let crc = crc16(&tampered[2..6]).to_le_bytes();
+    tampered[6..8].copy_from_slice(&crc);
+
+    for bad in [&plain[..plain_len + TAG_SIZE], &forged[..len], &tampered] {
+        let mut decoder = Decoder::with_key(key.clone());
+        assert!(decode(&mut decoder, bad).contains(&Err(DecodeError::BadTag)));
+        // The decoder recovers on the next good frame
+        assert_eq!(decode(&mut decoder, frame), [Ok(command)]);
+    }
+}
+
+type Queue = Rc<RefCell<VecDeque<u8>>>;
+
+/// One end of an in-memory wire
+struct Wire {
+    tx: Queue,
+    rx: Queue,
+}
+
+impl Transport for Wire {
+    type Error = Infallible;
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Infallible> {
+        self.tx.borrow_mut().extend(bytes);
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, Infallible> {
+        Ok(self.rx.borrow_mut().pop_front())
+    }
+}
+
+fn events(link: &mut Link<Wire, 4>) -> Vec<Result<Event, Error<Infallible>>> {
+    let mut events = Vec::new();
+    while let Some(event) = link.poll(0).transpose() {
+        events.push(event);
+    }
+    events
+}
+
+/// Two links with the same key, along with the bytes on their way to `b`
+fn pair(key: &Key) -> (Link<Wire, 4>, Link<Wire, 4>, Queue) {
+    let (a_to_b, b_to_a): (Queue, Queue) = Default::default();
+    let wire = |tx: &Queue, rx: &Queue| Wire {
+        tx: tx.clone(),
+        rx: rx.clone(),
+    };
+    (
+        Link::with_key(wire(&a_to_b, &b_to_a), 10, 2, key.clone(), 0x1234),
+        Link::with_key(wire(&b_to_a, &a_to_b), 10, 2, key.clone(), 0xABCD),
+        a_to_b,
+    )
+}
+
+fn command(level: u8) -> Message {
+    Message::Command(Command::SetBrightness(level))
+}
+
+fn hello() -> Message {
+    Message::Hello(Hello::new(Version::new(1, 0, 0)))
+}
+
+#[test]
+fn keyed_links_only_take_sequenced_commands() {
+    let key = Key::new(b"bench key");
+    let (mut a, mut b, a_to_b) = pair(&key);
+
+    // The command isn't tagged for b's nonce yet, b sends its nonce and the
+    // command goes out again
+    assert_eq!(a.send(command(3)), Err(Error::Unsequenced));
+    assert_eq!(a.send_reliable(command(3), 0), Ok(0));
+    assert_eq!(events(&mut b), [Err(Error::Decode(DecodeError::BadTag))]);
+    assert_eq!(events(&mut a), []);
+    assert_eq!(events(&mut b), [Ok(Event::Received(command(3)))]);
+    assert_eq!(events(&mut a), [Ok(Event::Delivered(0))]);
+
+    // A plain command tagged with the right key is refused
+    let mut buf = [0; MAX_FRAME_SIZE];
+    let len = key.encode(&command(4), &mut buf).unwrap();
+    a_to_b.borrow_mut().extend(&buf[..len]);
+    assert_eq!(events(&mut b), [Err(Error::Unsequenced)]);
+}
+
+#[test]
+fn replayed_commands_are_not_delivered_again() {
+    let key = Key::new(b"bench key");
+    let (mut a, mut b, a_to_b) = pair(&key);
+    let recorded = || a_to_b.borrow().iter().copied().collect::<Vec<u8>>();
+
+    a.send(hello()).unwrap();
+    let handshake = recorded();
+    assert_eq!(events(&mut b), [Ok(Event::Received(hello()))]);
+    assert_eq!(events(&mut a), []);
+
+    let reading = Message::Sensor(SensorMessage::Temperature(Temperature(20.0)));
+    assert_eq!(a.send_reliable(command(3), 0), Ok(0));
+    a.send(reading).unwrap();
+    let frames = recorded();
+    assert_eq!(
+        events(&mut b),
+        [
+            Ok(Event::Received(command(3))),
+            Ok(Event::Received(reading))
+        ]
+    );
+    assert_eq!(events(&mut a), [Ok(Event::Delivered(0))]);
+
+    // The command was already delivered, only the reading comes out again
+    a_to_b.borrow_mut().extend(&frames);
+    assert_eq!(events(&mut b), [Ok(Event::Received(reading))]);
+    assert_eq!(events(&mut a), []);
+
+    // A hello makes b accept sequence numbers from scratch, but only for
+    // frames tagged after it
+    a_to_b.borrow_mut().extend(&handshake);
+    a_to_b.borrow_mut().extend(&frames);
+    assert_eq!(
+        events(&mut b),
+        [
+            Ok(Event::Received(hello())),
+            Err(Error::Decode(DecodeError::BadTag)),
+            Ok(Event::Received(reading))
+        ]
+    );
+    assert_eq!(events(&mut a), []);
+
+    assert_eq!(a.send_reliable(command(5), 0), Ok(1));
+    assert_eq!(events(&mut b), [Ok(Event::Received(command(5)))]);
+    assert_eq!(events(&mut a), [Ok(Event::Delivered(1))]);
+}
+
+#[test]
+fn old_frames_are_rejected_once_sequence_numbers_wrap_around() {
+    let key = Key::new(b"bench key");
+    let (mut a, mut b, a_to_b) = pair(&key);
+    a.send(hello()).unwrap();
+    assert_eq!(events(&mut b), [Ok(Event::Received(hello()))]);
+    assert_eq!(events(&mut a), []);
+
+    let mut first = Vec::new();
+    for seq in 0..=u8::MAX {
+        assert_eq!(a.send_reliable(command(seq % 16), 0), Ok(seq));
+        if seq == 0 {
+            first = a_to_b.borrow().iter().copied().collect();
+        }
+        assert_eq!(events(&mut b), [Ok(Event::Received(command(seq % 16)))]);
+        assert_eq!(events(&mut a), [Ok(Event::Delivered(seq))]);
+    }
+
+    // b expects sequence number 0 again
+    a_to_b.borrow_mut().extend(&first);
+    assert_eq!(events(&mut b), [Err(Error::Decode(DecodeError::BadTag))]);
+}
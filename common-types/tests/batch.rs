@@ -0,0 +1,192 @@
+use common_types::batch::{self, Batch, DeltaBatch, DATA_SIZE};
+use common_types::frame::{self, Decoder};
+use common_types::message::{self, Message, MessageKind};
+use common_types::sample::Sequencer;
+use common_types::validate::ValidationError;
+use common_types::{CentiCelsius, SensorMessage, Temperature, WireMessage};
+
+fn acceleration(x: f32) -> SensorMessage {
+    SensorMessage::Acceleration { x, y: 0.0, z: 9.81 }
+}
+
+#[test]
+fn fills_up_to_capacity() {
+    let mut batch = Batch::new(MessageKind::Acceleration, 0).unwrap();
+    assert!(batch.is_empty());
+    assert_eq!(batch.capacity(), DATA_SIZE / 12);
+
+    for i in 0..batch.capacity() {
+        batch.push(acceleration(i as f32)).unwrap();
+    }
+    assert!(batch.is_full());
+    assert_eq!(batch.push(acceleration(0.0)), Err(batch::Error::Full));
+
+    let mut batch = Batch::new(MessageKind::TemperatureCenti, 0).unwrap();
+    assert_eq!(
+        batch.push(acceleration(0.0)),
+        Err(batch::Error::KindMismatch)
+    );
+    assert_eq!(
+        batch.push(SensorMessage::TemperatureCenti(CentiCelsius(9000))),
+        Err(batch::Error::Invalid(ValidationError::OutOfRange))
+    );
+    assert!(Batch::new(MessageKind::Hello, 0).is_none());
+}
+
+#[test]
+fn round_trips_through_a_frame() {
+    let mut sequencer = Sequencer::new();
+    sequencer.stamp(0, ());
+
+    let mut batch = Batch::new(MessageKind::TemperatureCenti, u32::MAX - 5).unwrap();
+    batch.period = 4;
+    for t in [2150, 2151, -40] {
+        batch
+            .push(SensorMessage::TemperatureCenti(CentiCelsius(t)))
+            .unwrap();
+    }
+    sequencer.stamp_batch(&mut batch);
+    assert_eq!(sequencer.stamp(0, ()).seq, 4);
+
+    let mut buf = [0; frame::MAX_FRAME_SIZE];
+    let n = Message::Batch(batch).to_frame(&mut buf).unwrap();
+    assert_eq!(n, frame::frame_size(batch::HEADER_SIZE + 3 * 2));
+
+    let mut decoder = Decoder::new();
+    let received = buf[..n]
+        .iter()
+        .filter_map(|&b| decoder.push(b).transpose())
+        .next();
+    let Some(Ok(Message::Batch(received))) = received else {
+        panic!("Batch wasn't received: {received:?}");
+    };
+    assert_eq!(received, batch);
+
+    let samples: Vec<_> = received
+        .samples()
+        .map(|sample| (sample.seq, sample.timestamp))
+        .collect();
+    assert_eq!(samples, [(1, u32::MAX - 5), (2, u32::MAX - 1), (3, 2)]);
+}
+
+#[test]
+fn rejects_bad_payloads() {
+    let mut batch = Batch::new(MessageKind::Acceleration, 0).unwrap();
+    batch.push(acceleration(1.0)).unwrap();
+    let mut buf = [0; Batch::SIZE];
+    let n = batch.encode_into(&mut buf).unwrap();
+
+    assert_eq!(
+        Batch::decode(&buf[..n - 1]),
+        Err(message::Error::InvalidLength)
+    );
+
+    // Turns the last axis into a NaN
+    buf[n - 2..n].copy_from_slice(&[0xC0, 0x7F]);
+    assert_eq!(
+        Batch::decode(&buf[..n]),
+        Err(message::Error::Invalid(ValidationError::NotFinite))
+    );
+
+    buf[batch::HEADER_SIZE - 1] = MessageKind::Sample.tag();
+    assert_eq!(
+        Batch::decode(&buf[..n]),
+        Err(message::Error::UnknownKind(MessageKind::Sample.tag()))
+    );
+}
+
+fn full_batch(kind: MessageKind, reading: impl Fn(usize) -> SensorMessage) -> Batch {
+    let mut batch = Batch::new(kind, 7).unwrap();
+    batch.seq = 100;
+    batch.period = 3;
+    for i in 0..batch.capacity() {
+        batch.push(reading(i)).unwrap();
+    }
+    batch
+}
+
+#[test]
+fn delta_encoding_round_trips_exactly() {
+    let batches = [
+        full_batch(MessageKind::TemperatureCenti, |i| {
+            let t = [-4000, 8500, 2150, 2149, 0, -1][i % 6];
+            SensorMessage::TemperatureCenti(CentiCelsius(t))
+        }),
+        full_batch(MessageKind::Temperature, |i| {
+            SensorMessage::Temperature(Temperature(-40.0 + i as f32 * 0.1))
+        }),
+        full_batch(MessageKind::AngularRate, |i| SensorMessage::AngularRate {
+            x: if i % 2 == 0 { 1e-30 } else { -4000.0 },
+            y: -0.0,
+            z: i as f32,
+        }),
+    ];
+
+    for batch in batches {
+        let delta = DeltaBatch(batch);
+        let mut buf = [0; DeltaBatch::SIZE];
+        match delta.encode_into(&mut buf) {
+            Ok(n) => assert_eq!(DeltaBatch::decode(&buf[..n]), Ok(delta)),
+            // Values jumping around can take more room than the plain batch
+            Err(e) => assert_eq!(e, frame::Error::PayloadTooLarge),
+        }
+    }
+}
+
+#[test]
+fn compresses_slowly_changing_readings() {
+    let batch = full_batch(MessageKind::TemperatureCenti, |i| {
+        SensorMessage::TemperatureCenti(CentiCelsius(2150 + (i % 3) as i16 - 1))
+    });
+    let Message::DeltaBatch(delta) = batch.compressed() else {
+        panic!("Batch wasn't delta encoded");
+    };
+    let mut buf = [0; DeltaBatch::SIZE];
+    let n = delta.encode_into(&mut buf).unwrap();
+    // The first reading takes two bytes, every other one a single byte
+    assert_eq!(n, batch::HEADER_SIZE + batch.len() + 1);
+
+    let batch = full_batch(MessageKind::Acceleration, |i| {
+        let x = if i % 2 == 0 { 100.0 } else { -100.0 };
+        SensorMessage::Acceleration { x, y: -x, z: x }
+    });
+    assert_eq!(batch.compressed(), Message::Batch(batch));
+}
+
+#[test]
+fn delta_decoding_rejects_bad_payloads() {
+    let batch = full_batch(MessageKind::TemperatureCenti, |_| {
+        SensorMessage::TemperatureCenti(CentiCelsius(0))
+    });
+    let mut buf = [0; DeltaBatch::SIZE];
+    batch.encode_into(&mut buf).unwrap();
+    let header = &buf[..batch::HEADER_SIZE];
+
+    let decode = |values: &[u8]| {
+        let mut payload = header.to_vec();
+        payload.extend_from_slice(values);
+        DeltaBatch::decode(&payload)
+    };
+    // Truncated varint
+    assert_eq!(decode(&[0x80]), Err(message::Error::InvalidLength));
+    // Varint overflowing a u32
+    assert_eq!(
+        decode(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F]),
+        Err(message::Error::InvalidLength)
+    );
+    // Value that doesn't fit in an i16
+    assert_eq!(
+        decode(&[0x80, 0x80, 0x04]),
+        Err(message::Error::InvalidLength)
+    );
+    // Reading out of range
+    assert_eq!(
+        decode(&[0xA0, 0x8D, 0x01]),
+        Err(message::Error::Invalid(ValidationError::OutOfRange))
+    );
+    // More readings than a batch holds
+    assert_eq!(
+        decode(&[0; batch::DATA_SIZE / 2 + 1]),
+        Err(message::Error::InvalidLength)
+    );
+}
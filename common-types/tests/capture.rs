@@ -0,0 +1,149 @@
+#![cfg(feature = "std")]
+
+use common_types::capture::{Decoded, Error, Messages, Reader, Record, Writer, MAGIC};
+use common_types::command::Command;
+use common_types::frame::{DecodeError, Decoder, MAX_FRAME_SIZE};
+use common_types::message::Message;
+use common_types::{CentiCelsius, SensorMessage};
+
+fn frame(message: impl Into<Message>) -> Vec<u8> {
+    let mut buf = [0; MAX_FRAME_SIZE];
+    let len = message.into().to_frame(&mut buf).unwrap();
+    buf[..len].to_vec()
+}
+
+#[test]
+fn records_round_trip() {
+    let mut writer = Writer::new(Vec::new()).unwrap();
+    writer.write(0, &[1, 2, 3]).unwrap();
+    writer.write(1_500, &[]).unwrap();
+    writer.write(u64::MAX, &[4]).unwrap();
+    let file = writer.into_inner();
+    assert_eq!(&file[..6], &[b'L', b'C', b'A', b'P', 1, 0]);
+
+    let records: Vec<Record> = Reader::new(&file[..])
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        records,
+        [
+            Record {
+                timestamp: 0,
+                bytes: vec![1, 2, 3]
+            },
+            Record {
+                timestamp: u64::MAX,
+                bytes: vec![4]
+            },
+        ]
+    );
+
+    assert!(matches!(
+        Reader::new(&b"PCAP\x01\x00"[..]),
+        Err(Error::NotACapture)
+    ));
+    assert!(matches!(
+        Reader::new(&[&MAGIC[..], &[2, 0]].concat()[..]),
+        Err(Error::UnsupportedVersion(2))
+    ));
+    let mut reader = Reader::new(&file[..file.len() - 1]).unwrap();
+    reader.read_record().unwrap();
+    assert!(matches!(reader.read_record(), Err(Error::Truncated)));
+}
+
+#[test]
+fn replays_through_the_decoder() {
+    let reading = SensorMessage::TemperatureCenti(CentiCelsius(2150));
+    let command = Command::StopStreaming;
+    let good = frame(reading);
+    let mut corrupted = frame(command);
+    corrupted[4] ^= 0xFF;
+
+    // Garbage, a frame split across reads, a corrupted frame and a good one
+    // read along with it
+    let mut writer = Writer::new(Vec::new()).unwrap();
+    writer.write(10, &[0x00, 0xAA]).unwrap();
+    writer.write(20, &good[..3]).unwrap();
+    writer.write(30, &good[3..]).unwrap();
+    writer
+        .write(40, &[&corrupted[..], &frame(command)[..]].concat())
+        .unwrap();
+    let file = writer.into_inner();
+
+    let decoded: Vec<Decoded> = Reader::new(&file[..])
+        .unwrap()
+        .messages()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        decoded,
+        [
+            Decoded {
+                timestamp: 30,
+                message: Ok(reading.into())
+            },
+            Decoded {
+                timestamp: 40,
+                message: Err(DecodeError::BadCrc)
+            },
+            Decoded {
+                timestamp: 40,
+                message: Ok(command.into())
+            },
+        ]
+    );
+}
+
+#[test]
+fn replays_frames_hidden_at_the_end() {
+    // A corrupted header announcing more bytes than the capture has left,
+    // followed by a good frame
+    let reading = SensorMessage::TemperatureCenti(CentiCelsius(-500));
+    let mut writer = Writer::new(Vec::new()).unwrap();
+    writer.write(10, &[0xAA, 0x55, 0x01, 100]).unwrap();
+    writer.write(20, &frame(reading)).unwrap();
+    let file = writer.into_inner();
+
+    let decoded: Vec<Decoded> = Reader::new(&file[..])
+        .unwrap()
+        .messages()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        decoded,
+        [Decoded {
+            timestamp: 20,
+            message: Ok(reading.into())
+        }]
+    );
+}
+
+#[test]
+fn decodes_records_that_were_never_written() {
+    let reading = SensorMessage::TemperatureCenti(CentiCelsius(100));
+    let good = frame(reading);
+    let records = vec![
+        Ok(Record {
+            timestamp: 5,
+            bytes: good[..2].to_vec(),
+        }),
+        Ok(Record {
+            timestamp: 7,
+            bytes: good[2..].to_vec(),
+        }),
+        Err(Error::Truncated),
+    ];
+
+    let mut messages = Messages::new(records.into_iter(), Decoder::new());
+    assert_eq!(
+        messages.next().unwrap().unwrap(),
+        Decoded {
+            timestamp: 7,
+            message: Ok(reading.into())
+        }
+    );
+    // Errors reading the records are passed on
+    assert!(matches!(messages.next(), Some(Err(Error::Truncated))));
+    assert!(messages.next().is_none());
+}
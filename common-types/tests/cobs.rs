@@ -0,0 +1,143 @@
+use common_types::cobs::{self, DecodeError, Decoder};
+use common_types::message::Message;
+use common_types::{CentiCelsius, SensorMessage, Temperature};
+
+fn round_trip(data: &[u8], expected: &[u8]) {
+    let mut encoded = [0; 512];
+    let n = cobs::encode(data, &mut encoded).unwrap();
+    assert_eq!(&encoded[..n], expected);
+    assert!(n <= cobs::max_encoded_len(data.len()));
+    assert!(!encoded[..n].contains(&0));
+
+    let mut decoded = [0; 512];
+    let m = cobs::decode(&encoded[..n], &mut decoded).unwrap();
+    assert_eq!(&decoded[..m], data);
+}
+
+#[test]
+fn known_vectors() {
+    round_trip(&[], &[0x01]);
+    round_trip(&[0x00], &[0x01, 0x01]);
+    round_trip(&[0x00, 0x00], &[0x01, 0x01, 0x01]);
+    round_trip(&[0x11, 0x22, 0x00, 0x33], &[0x03, 0x11, 0x22, 0x02, 0x33]);
+    round_trip(&[0x11, 0x22, 0x33, 0x44], &[0x05, 0x11, 0x22, 0x33, 0x44]);
+    round_trip(&[0x11, 0x00, 0x00, 0x00], &[0x02, 0x11, 0x01, 0x01, 0x01]);
+}
+
+#[test]
+fn long_blocks() {
+    let data: Vec<u8> = (0x01..=0xFE).collect();
+    let mut expected = vec![0xFF];
+    expected.extend(0x01..=0xFE);
+    round_trip(&data, &expected);
+
+    let data: Vec<u8> = (0x00..=0xFE).collect();
+    let mut expected = vec![0x01, 0xFF];
+    expected.extend(0x01..=0xFE);
+    round_trip(&data, &expected);
+
+    let data: Vec<u8> = (0x01..=0xFF).collect();
+    let mut expected = vec![0xFF];
+    expected.extend(0x01..=0xFE);
+    expected.extend([0x02, 0xFF]);
+    round_trip(&data, &expected);
+}
+
+#[test]
+fn rejects_malformed_data() {
+    let mut out = [0; 8];
+    assert_eq!(
+        cobs::decode(&[0x03, 0x11], &mut out),
+        Err(cobs::Error::Malformed)
+    );
+    assert_eq!(
+        cobs::decode(&[0x02, 0x00], &mut out),
+        Err(cobs::Error::Malformed)
+    );
+    assert_eq!(
+        cobs::encode(&[1, 2, 3], &mut out[..3]),
+        Err(cobs::Error::BufferTooSmall)
+    );
+}
+
+#[test]
+fn message_round_trip() {
+    let message = Message::Sensor(SensorMessage::Temperature(Temperature(-12.25)));
+    let mut buf = [0; cobs::MAX_FRAME_SIZE];
+    let n = cobs::encode_message(&message, &mut buf).unwrap();
+    assert_eq!(buf[n - 1], cobs::DELIMITER);
+
+    let mut rx = [0; cobs::MAX_FRAME_SIZE];
+    let mut decoder = Decoder::new(&mut rx);
+    let received: Vec<_> = buf[..n].iter().map(|&b| decoder.push(b)).collect();
+    assert!(received[..n - 1].iter().all(|r| *r == Ok(None)));
+    assert_eq!(received[n - 1], Ok(Some(message)));
+}
+
+#[test]
+fn every_sensor_message_round_trips() {
+    let messages = [
+        SensorMessage::Temperature(Temperature(36.6)),
+        SensorMessage::TemperatureCenti(CentiCelsius(-1234)),
+        SensorMessage::Acceleration {
+            x: 0.0,
+            y: -9.81,
+            z: 0.5,
+        },
+        SensorMessage::AngularRate {
+            x: 125.0,
+            y: 0.0,
+            z: -0.25,
+        },
+    ];
+
+    let mut rx = [0; cobs::MAX_FRAME_SIZE];
+    let mut decoder = Decoder::new(&mut rx);
+    for reading in messages {
+        let message = Message::Sensor(reading);
+        let mut buf = [0; cobs::MAX_FRAME_SIZE];
+        let n = cobs::encode_message(&message, &mut buf).unwrap();
+        let received = buf[..n]
+            .iter()
+            .filter_map(|&b| decoder.push(b).transpose())
+            .next();
+        assert_eq!(received, Some(Ok(message)));
+    }
+}
+
+#[test]
+fn decoder_resyncs_after_corruption() {
+    let message = Message::Sensor(SensorMessage::Temperature(Temperature(21.5)));
+    let mut buf = [0; cobs::MAX_FRAME_SIZE];
+    let n = cobs::encode_message(&message, &mut buf).unwrap();
+
+    let mut stream = vec![0x42, 0x13];
+    stream.extend_from_slice(&buf[..n]);
+    let mut corrupted = buf[..n].to_vec();
+    corrupted[n - 4] ^= 0x40;
+    stream.extend_from_slice(&corrupted);
+    stream.extend_from_slice(&buf[..n]);
+
+    let mut rx = [0; cobs::MAX_FRAME_SIZE];
+    let mut decoder = Decoder::new(&mut rx);
+    let results: Vec<_> = stream
+        .iter()
+        .filter_map(|&b| decoder.push(b).transpose())
+        .collect();
+
+    assert_eq!(results.len(), 3);
+    // Leading garbage ends up glued to the first frame
+    assert_eq!(results[0], Err(DecodeError::Malformed));
+    assert_eq!(results[1], Err(DecodeError::BadCrc));
+    assert_eq!(results[2], Ok(message));
+}
+
+#[test]
+fn decoder_reports_oversize_frames() {
+    let mut rx = [0; 4];
+    let mut decoder = Decoder::new(&mut rx);
+    for b in 1..=8 {
+        assert_eq!(decoder.push(b), Ok(None));
+    }
+    assert_eq!(decoder.push(0), Err(DecodeError::Oversize));
+}
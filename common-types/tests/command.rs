@@ -0,0 +1,118 @@
+use common_types::command::{AccelOdr, Command, Opcode, Response, Status, MAX_BRIGHTNESS};
+use common_types::frame::{DecodeError, Decoder};
+use common_types::message::{Error, Message, MessageKind};
+use common_types::validate::ValidationError;
+use common_types::WireMessage;
+
+const COMMANDS: [Command; 7] = [
+    Command::SetAccelOdr(AccelOdr::Hz833),
+    Command::SetBrightness(MAX_BRIGHTNESS),
+    Command::RequestSample,
+    Command::StartStreaming,
+    Command::StopStreaming,
+    Command::RequestDescriptor,
+    Command::SetSummaryPeriod(60),
+];
+
+fn encode(message: &impl WireMessage) -> Vec<u8> {
+    let mut buf = [0; Message::SIZE];
+    let len = message.encode_into(&mut buf).unwrap();
+    buf[..len].to_vec()
+}
+
+#[test]
+fn commands_start_with_their_opcode() {
+    for command in COMMANDS {
+        let bytes = encode(&command);
+        assert_eq!(bytes[0], command.opcode() as u8);
+        assert_eq!(Command::decode(&bytes), Ok(command));
+        assert_eq!(Opcode::decode(&bytes[..1]), Ok(command.opcode()));
+
+        let response = Response::ack(&command);
+        assert_eq!(encode(&response), [bytes[0], 0]);
+        assert!(response.is_ack());
+        assert!(!Response::new(&command, Status::Failed).is_ack());
+    }
+
+    // Every rate has its own byte
+    let odrs = [
+        AccelOdr::Hz12_5,
+        AccelOdr::Hz26,
+        AccelOdr::Hz52,
+        AccelOdr::Hz104,
+        AccelOdr::Hz208,
+        AccelOdr::Hz416,
+        AccelOdr::Hz833,
+    ];
+    for (i, odr) in odrs.into_iter().enumerate() {
+        assert_eq!(encode(&Command::SetAccelOdr(odr)), [0x01, i as u8]);
+    }
+}
+
+#[test]
+fn rejects_unknown_bytes() {
+    let unknown = Error::Invalid(ValidationError::UnknownVariant);
+    assert_eq!(Command::decode(&[0x00]), Err(unknown));
+    assert_eq!(Command::decode(&[0x08]), Err(unknown));
+    assert_eq!(Command::decode(&[0xFF, 0]), Err(unknown));
+    assert_eq!(Command::decode(&[0x01, 7]), Err(unknown));
+    assert_eq!(Command::decode(&[0x01, 0xFF]), Err(unknown));
+
+    assert_eq!(Response::decode(&[0x08, 0]), Err(unknown));
+    assert_eq!(Response::decode(&[0x01, 4]), Err(unknown));
+    assert_eq!(Response::decode(&[0x01, 0xFF]), Err(unknown));
+
+    assert_eq!(
+        Command::decode(&[0x02, MAX_BRIGHTNESS + 1]),
+        Err(Error::Invalid(ValidationError::OutOfRange))
+    );
+}
+
+#[test]
+fn rejects_wrong_lengths() {
+    assert_eq!(Command::decode(&[]), Err(Error::InvalidLength));
+    for command in COMMANDS {
+        let bytes = encode(&command);
+        // Missing argument, or one the command doesn't take
+        if bytes.len() > 1 {
+            assert_eq!(Command::decode(&bytes[..1]), Err(Error::InvalidLength));
+        }
+        let longer = [&bytes[..], &[0]].concat();
+        assert_eq!(Command::decode(&longer), Err(Error::InvalidLength));
+    }
+
+    assert_eq!(Response::decode(&[]), Err(Error::InvalidLength));
+    assert_eq!(Response::decode(&[0x01]), Err(Error::InvalidLength));
+    assert_eq!(Response::decode(&[0x01, 0, 0]), Err(Error::InvalidLength));
+}
+
+#[test]
+fn bad_frames_are_reported() {
+    let frame = |kind: MessageKind, payload: &[u8]| {
+        let mut buf = [0; 16];
+        let len = common_types::frame::encode(kind, payload, &mut buf).unwrap();
+        buf[..len].to_vec()
+    };
+    let stream = [
+        frame(MessageKind::Command, &[0x09]),
+        frame(MessageKind::Command, &[0x04, 0]),
+        frame(MessageKind::Response, &[0x04, 9]),
+        frame(MessageKind::Command, &[0x04]),
+    ]
+    .concat();
+
+    let mut decoder = Decoder::new();
+    let results: Vec<_> = stream
+        .iter()
+        .filter_map(|&byte| decoder.push(byte).transpose())
+        .collect();
+    assert_eq!(
+        results,
+        [
+            Err(DecodeError::Invalid(ValidationError::UnknownVariant)),
+            Err(DecodeError::InvalidLength),
+            Err(DecodeError::Invalid(ValidationError::UnknownVariant)),
+            Ok(Message::Command(Command::StartStreaming)),
+        ]
+    );
+}
@@ -0,0 +1,141 @@
+use common_types::command::{AccelOdr, Command, Opcode, Response, Status};
+use common_types::message;
+use common_types::validate::{Validate, ValidationError};
+use common_types::WireMessage;
+
+#[derive(Debug, PartialEq, WireMessage)]
+struct Reading {
+    id: u16,
+    value: f32,
+    valid: bool,
+}
+
+#[derive(Debug, PartialEq, WireMessage)]
+struct Wrapped<T>(u8, T);
+
+#[derive(Debug, PartialEq, WireMessage)]
+struct Empty;
+
+#[derive(Debug, PartialEq, WireMessage)]
+#[repr(u8)]
+enum Event {
+    Reset,
+    Level(i16),
+    Moved { x: i8, y: i8 } = 7,
+    Stopped,
+}
+
+#[derive(Debug, PartialEq, WireMessage)]
+#[wire(validate)]
+struct Percent(u8);
+
+impl Validate for Percent {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.0 <= 100 {
+            Ok(())
+        } else {
+            Err(ValidationError::OutOfRange)
+        }
+    }
+}
+
+fn encode<T: WireMessage>(value: &T) -> Vec<u8> {
+    let mut buf = vec![0; T::SIZE];
+    let len = value.encode_into(&mut buf).unwrap();
+    buf.truncate(len);
+    buf
+}
+
+fn round_trip<T: WireMessage + PartialEq + core::fmt::Debug>(value: T, expected: &[u8]) {
+    let bytes = encode(&value);
+    assert_eq!(bytes, expected);
+    assert!(bytes.len() <= T::SIZE);
+    assert_eq!(T::decode(&bytes), Ok(value));
+}
+
+#[test]
+fn structs_are_little_endian_fields_in_order() {
+    assert_eq!(Reading::SIZE, 7);
+    round_trip(
+        Reading {
+            id: 0x0102,
+            value: 1.0,
+            valid: true,
+        },
+        &[0x02, 0x01, 0x00, 0x00, 0x80, 0x3F, 0x01],
+    );
+    round_trip(Wrapped(9, -2i32), &[9, 0xFE, 0xFF, 0xFF, 0xFF]);
+    round_trip(Empty, &[]);
+}
+
+#[test]
+fn enums_start_with_their_tag() {
+    assert_eq!(Event::SIZE, 3);
+    round_trip(Event::Reset, &[0]);
+    round_trip(Event::Level(-300), &[1, 0xD4, 0xFE]);
+    round_trip(Event::Moved { x: 1, y: -1 }, &[7, 1, 0xFF]);
+    round_trip(Event::Stopped, &[8]);
+}
+
+#[test]
+fn rejects_bad_input() {
+    let invalid_length = Some(message::Error::InvalidLength);
+    assert_eq!(Reading::decode(&[0; 6]).err(), invalid_length);
+    assert_eq!(Reading::decode(&[0; 8]).err(), invalid_length);
+    assert_eq!(Event::decode(&[]).err(), invalid_length);
+    assert_eq!(Event::decode(&[0, 0]).err(), invalid_length);
+    assert_eq!(Event::decode(&[1, 0]).err(), invalid_length);
+    assert_eq!(Empty::decode(&[0]).err(), invalid_length);
+
+    assert_eq!(
+        Event::decode(&[2]),
+        Err(message::Error::Invalid(ValidationError::UnknownVariant))
+    );
+    assert_eq!(
+        Percent::decode(&[101]),
+        Err(message::Error::Invalid(ValidationError::OutOfRange))
+    );
+
+    assert_eq!(
+        Reading::decode(&[0, 0, 0, 0, 0, 0, 2]),
+        Err(message::Error::Invalid(ValidationError::UnknownVariant))
+    );
+
+    let mut buf = [0; 6];
+    assert_eq!(
+        Reading {
+            id: 0,
+            value: 0.0,
+            valid: false,
+        }
+        .encode_into(&mut buf),
+        Err(common_types::frame::Error::BufferTooSmall)
+    );
+}
+
+#[test]
+fn commands_keep_their_layout() {
+    round_trip(Command::SetAccelOdr(AccelOdr::Hz104), &[0x01, 3]);
+    round_trip(Command::SetBrightness(15), &[0x02, 15]);
+    round_trip(Command::StopStreaming, &[0x05]);
+    round_trip(
+        Response {
+            opcode: Opcode::SetBrightness,
+            status: Status::Unsupported,
+        },
+        &[0x02, 3],
+    );
+
+    assert_eq!(
+        Command::decode(&[0x02, 16]),
+        Err(message::Error::Invalid(ValidationError::OutOfRange))
+    );
+    assert_eq!(
+        Command::decode(&[0x01, 7]),
+        Err(message::Error::Invalid(ValidationError::UnknownVariant))
+    );
+    assert_eq!(
+        Command::decode(&[0x03, 0]),
+        Err(message::Error::InvalidLength)
+    );
+}
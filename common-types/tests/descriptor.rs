@@ -0,0 +1,67 @@
+use common_types::command::{AccelOdr, Command};
+use common_types::descriptor::{BoardType, Descriptor, OdrSet, SensorPart};
+use common_types::message::{Error, Message, MessageKind};
+use common_types::validate::ValidationError;
+use common_types::WireMessage;
+
+#[test]
+fn descriptor_layout() {
+    let descriptor = Descriptor {
+        unique_id: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        board: BoardType::NucleoH745ZiQ,
+        sensor: SensorPart::Ism330dhcx,
+        accel_odrs: OdrSet::EMPTY.with(AccelOdr::Hz12_5).with(AccelOdr::Hz833),
+        display: true,
+    };
+    let mut buf = [0; Message::SIZE];
+    let len = Message::from(descriptor).encode_into(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..len],
+        &[
+            MessageKind::Descriptor.tag(),
+            1,
+            2,
+            3,
+            4,
+            5,
+            6,
+            7,
+            8,
+            9,
+            10,
+            11,
+            12,
+            1,
+            1,
+            0b100_0001,
+            1
+        ]
+    );
+    assert_eq!(
+        Message::decode(&buf[..len]),
+        Ok(Message::Descriptor(descriptor))
+    );
+
+    // Truncated descriptors and unknown parts are rejected
+    assert_eq!(
+        Descriptor::decode(&buf[1..len - 1]),
+        Err(Error::InvalidLength)
+    );
+    buf[14] = 9;
+    assert_eq!(
+        Descriptor::decode(&buf[1..len]),
+        Err(Error::Invalid(ValidationError::UnknownVariant))
+    );
+}
+
+#[test]
+fn odr_sets() {
+    let all = OdrSet::all();
+    assert_eq!(all, OdrSet(0x7F));
+    assert!(all.contains(AccelOdr::Hz833));
+    assert!(!OdrSet::EMPTY.contains(AccelOdr::Hz52));
+
+    let mut buf = [0; Command::SIZE];
+    let len = Command::RequestDescriptor.encode_into(&mut buf).unwrap();
+    assert_eq!(&buf[..len], &[0x06]);
+}
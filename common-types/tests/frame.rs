@@ -0,0 +1,140 @@
+use common_types::frame::{self, DecodeError, Decoded, Decoder, MAX_FRAME_SIZE};
+use common_types::message::{Message, MessageKind};
+use common_types::{SensorMessage, Temperature};
+
+type Outcome = Result<Message, DecodeError>;
+
+fn reading(temp: f32) -> Message {
+    SensorMessage::Temperature(Temperature(temp)).into()
+}
+
+fn frame(message: Message) -> Vec<u8> {
+    let mut buf = [0; MAX_FRAME_SIZE];
+    let len = message.to_frame(&mut buf).unwrap();
+    buf[..len].to_vec()
+}
+
+/// Everything the decoder reports while fed `bytes`, along with the index of
+/// the byte that was fed last
+fn decode_all(decoder: &mut Decoder, bytes: &[u8]) -> Vec<(usize, Outcome)> {
+    let mut outcomes = Vec::new();
+    for (i, &byte) in bytes.iter().enumerate() {
+        let mut result = decoder.push(byte);
+        while let Some(outcome) = result.transpose() {
+            outcomes.push((i, outcome));
+            result = decoder.poll();
+        }
+    }
+    outcomes
+}
+
+#[test]
+fn frame_layout() {
+    let message = reading(21.5);
+    let bytes = frame(message);
+    let mut expected = vec![0xAA, 0x55, MessageKind::Temperature.tag(), 4];
+    expected.extend(21.5f32.to_le_bytes());
+    let crc = common_types::crc::crc16(&expected[2..]);
+    expected.extend(crc.to_le_bytes());
+    assert_eq!(bytes, expected);
+
+    let stream = [&[0x00, 0xAA][..], &bytes].concat();
+    match frame::decode(&stream) {
+        Decoded::Frame { frame, consumed } => {
+            assert_eq!(frame.kind, MessageKind::Temperature.tag());
+            assert_eq!(frame.payload, &21.5f32.to_le_bytes());
+            assert_eq!(consumed, stream.len());
+        }
+        decoded => panic!("{:?}", decoded),
+    }
+    assert_eq!(
+        frame::decode(&stream[..stream.len() - 1]),
+        Decoded::Incomplete { discard: 2 }
+    );
+
+    let mut small = [0; 4];
+    assert_eq!(
+        frame::encode(MessageKind::Temperature, &[0; 4], &mut small),
+        Err(frame::Error::BufferTooSmall)
+    );
+    assert_eq!(
+        frame::encode(MessageKind::Temperature, &[0; 129], &mut [0; 256]),
+        Err(frame::Error::PayloadTooLarge)
+    );
+}
+
+#[test]
+fn finds_frames_among_garbage() {
+    let first = reading(1.0);
+    let second = reading(2.0);
+    let stream = [
+        &[0x00, 0xAA, 0xAA, 0x13, 0x55][..],
+        &frame(first),
+        &[0xAA, 0xAA, 0x42],
+        &frame(second),
+        &[0xFF],
+    ]
+    .concat();
+
+    let mut decoder = Decoder::new();
+    let outcomes: Vec<Outcome> = decode_all(&mut decoder, &stream)
+        .into_iter()
+        .map(|(_, outcome)| outcome)
+        .collect();
+    assert_eq!(outcomes, [Ok(first), Ok(second)]);
+    assert_eq!(decoder.finish(), Ok(None));
+}
+
+#[test]
+fn resyncs_after_bad_frames() {
+    // A corrupted frame whose payload is a whole frame
+    let inner = reading(36.6);
+    let inner_bytes = frame(inner);
+    let mut outer = vec![
+        0xAA,
+        0x55,
+        MessageKind::Sample.tag(),
+        inner_bytes.len() as u8,
+    ];
+    outer.extend(&inner_bytes);
+    outer.extend([0xDE, 0xAD]);
+
+    // The hidden frame comes out right after the corrupted one, without
+    // waiting for more bytes
+    let mut decoder = Decoder::new();
+    let outcomes = decode_all(&mut decoder, &outer);
+    let last = outer.len() - 1;
+    assert_eq!(
+        outcomes,
+        [(last, Err(DecodeError::BadCrc)), (last, Ok(inner))]
+    );
+
+    // An oversized header is dropped and the next frame decoded as usual
+    let stream = [&[0xAA, 0x55, 0x01, 0xFF][..], &frame(inner)].concat();
+    let outcomes = decode_all(&mut decoder, &stream);
+    assert_eq!(
+        outcomes,
+        [
+            (3, Err(DecodeError::Oversize(0xFF))),
+            (stream.len() - 1, Ok(inner))
+        ]
+    );
+}
+
+#[test]
+fn finishes_at_end_of_input() {
+    // The header announces more bytes than the input has left, so the frame
+    // inside only shows up when the decoder gives up on waiting
+    let inner = reading(-4.0);
+    let stream = [&[0xAA, 0x55, 0x01, 100][..], &frame(inner), &[0xAA]].concat();
+
+    let mut decoder = Decoder::new();
+    assert_eq!(decode_all(&mut decoder, &stream), []);
+    assert_eq!(decoder.poll(), Ok(None));
+    assert_eq!(decoder.finish(), Ok(Some(inner)));
+    assert_eq!(decoder.finish(), Ok(None));
+
+    // The decoder is empty afterwards
+    let outcomes = decode_all(&mut decoder, &frame(inner));
+    assert_eq!(outcomes.len(), 1);
+}
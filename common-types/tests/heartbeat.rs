@@ -0,0 +1,34 @@
+use common_types::heartbeat::{Heartbeat, HeartbeatTimer, LinkMonitor, LinkState};
+
+#[test]
+fn heartbeats_are_sent_every_period() {
+    let mut timer = HeartbeatTimer::new(100);
+    assert_eq!(timer.poll(5), Some(Heartbeat { seq: 0 }));
+    assert_eq!(timer.poll(104), None);
+    assert_eq!(timer.poll(105), Some(Heartbeat { seq: 1 }));
+
+    // The tick count wrapping around doesn't stop them
+    let mut timer = HeartbeatTimer::new(100);
+    assert!(timer.poll(u32::MAX - 10).is_some());
+    assert_eq!(timer.poll(50), None);
+    assert!(timer.poll(90).is_some());
+}
+
+#[test]
+fn link_degrades_then_gets_lost() {
+    let mut monitor = LinkMonitor::new(100, 300);
+    assert_eq!(monitor.state(), LinkState::Lost);
+    assert_eq!(monitor.update(1000), None);
+
+    assert_eq!(monitor.received(1000), Some(LinkState::Connected));
+    assert_eq!(monitor.update(1099), None);
+    assert_eq!(monitor.update(1100), Some(LinkState::Degraded));
+    assert_eq!(monitor.received(1150), Some(LinkState::Connected));
+    assert_eq!(monitor.received(1200), None);
+
+    assert_eq!(monitor.update(1500), Some(LinkState::Lost));
+    // Long after, the tick count wraps around and looks recent again
+    assert_eq!(monitor.update(1250), None);
+    assert_eq!(monitor.state(), LinkState::Lost);
+    assert_eq!(monitor.received(1600), Some(LinkState::Connected));
+}
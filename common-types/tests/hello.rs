@@ -0,0 +1,79 @@
+use common_types::hello::{
+    Compatibility, Hello, KindSet, ProtocolVersion, Version, PROTOCOL_VERSION,
+};
+use common_types::message::{self, Message, MessageKind};
+use common_types::WireMessage;
+
+fn peer(major: u8, minor: u8, kinds: KindSet) -> Hello {
+    Hello {
+        protocol: ProtocolVersion { major, minor },
+        firmware: Version::new(0, 3, 1),
+        kinds,
+    }
+}
+
+#[test]
+fn hello_layout() {
+    let hello = peer(1, 2, KindSet::EMPTY.with(MessageKind::Hello));
+    assert_eq!(hello.to_bytes(), [1, 2, 0, 3, 1, 0x20, 0, 0, 0]);
+    assert_eq!(Hello::from_bytes(hello.to_bytes()), hello);
+
+    // Each kind is the bit given by its tag, whatever order it's listed in
+    let kinds = KindSet::EMPTY
+        .with(MessageKind::Stats)
+        .with(MessageKind::Temperature)
+        .with(MessageKind::Command);
+    assert_eq!(kinds.0, 1 << 0x16 | 1 << 0x06 | 1 << 0x01);
+    assert_eq!(&peer(1, 0, kinds).to_bytes()[5..], &[0x42, 0, 0x40, 0]);
+    for kind in MessageKind::ALL {
+        assert!(KindSet::all().contains(kind));
+        assert_eq!(KindSet::EMPTY.with(kind).0, 1 << kind.tag());
+    }
+
+    // Fields appended by newer versions are skipped, missing ones aren't
+    let mut bytes = [0; Message::SIZE];
+    let len = hello.encode_into(&mut bytes).unwrap();
+    assert_eq!(Hello::decode(&bytes[..len + 3]), Ok(hello));
+    assert_eq!(
+        Hello::decode(&bytes[..len - 1]),
+        Err(message::Error::InvalidLength)
+    );
+}
+
+#[test]
+fn checks_what_the_peer_understands() {
+    let local = Hello::new(common_types::firmware_version!());
+    assert_eq!(local.protocol, PROTOCOL_VERSION);
+    assert_eq!(local.firmware, Version::new(0, 1, 0));
+
+    // Minor versions and firmware don't matter as long as the kinds match
+    let newer = peer(
+        PROTOCOL_VERSION.major,
+        PROTOCOL_VERSION.minor + 1,
+        local.kinds,
+    );
+    assert_eq!(local.check(&newer), Compatibility::Full);
+    // Nor do messages only the peer knows about
+    let more = KindSet(local.kinds.0 | 1 << 31);
+    assert_eq!(
+        local.check(&peer(PROTOCOL_VERSION.major, 0, more)),
+        Compatibility::Full
+    );
+
+    // An older peer is used for what both ends know about
+    let older = KindSet::EMPTY
+        .with(MessageKind::Hello)
+        .with(MessageKind::Temperature);
+    let compatibility = local.check(&peer(PROTOCOL_VERSION.major, 0, older));
+    assert_eq!(compatibility, Compatibility::Degraded { common: older });
+    assert!(compatibility.usable().contains(MessageKind::Temperature));
+    assert!(!compatibility.usable().contains(MessageKind::Command));
+
+    // Only the handshake is possible across major versions
+    let other = peer(PROTOCOL_VERSION.major + 1, 0, local.kinds);
+    assert_eq!(local.check(&other), Compatibility::Incompatible);
+    assert_eq!(
+        Compatibility::Incompatible.usable(),
+        KindSet::EMPTY.with(MessageKind::Hello)
+    );
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "postcard")]
+
+use common_types::command::Command;
+use common_types::hello::Hello;
+use common_types::message::{Message, MessageKind};
+use common_types::postcard::{self, Error};
+use common_types::sample::Sample;
+use common_types::validate::ValidationError;
+use common_types::{firmware_version, Batch, CentiCelsius, SensorMessage, Temperature};
+
+fn batch() -> Batch {
+    let mut batch = Batch::new(MessageKind::AngularRate, 1000).unwrap();
+    batch.period = 20;
+    for x in [0.5, -0.5] {
+        let reading = SensorMessage::AngularRate { x, y: 0.0, z: 1.0 };
+        batch.push(reading).unwrap();
+    }
+    batch
+}
+
+#[test]
+fn messages_round_trip() {
+    let messages = [
+        Message::Sensor(SensorMessage::Temperature(Temperature(21.5))),
+        Message::Sample(Sample {
+            seq: 65535,
+            timestamp: 123_456,
+            value: SensorMessage::TemperatureCenti(CentiCelsius(-4000)),
+        }),
+        Message::Hello(Hello::new(firmware_version!())),
+        Message::Command(Command::SetBrightness(7)),
+        Message::Batch(batch()),
+    ];
+
+    for message in messages {
+        let mut buf = [0; 128];
+        let len = postcard::encode(&message, &mut buf).unwrap();
+        assert_eq!(postcard::decode::<Message>(&buf[..len]), Ok(message));
+    }
+}
+
+#[test]
+fn rejects_invalid_messages() {
+    let mut buf = [0; 64];
+    let len = postcard::encode(&Message::Command(Command::SetBrightness(16)), &mut buf).unwrap();
+    assert_eq!(
+        postcard::decode::<Message>(&buf[..len]),
+        Err(Error::Invalid(ValidationError::OutOfRange))
+    );
+
+    let len = postcard::encode(&Message::Command(Command::StopStreaming), &mut buf).unwrap();
+    assert_eq!(
+        postcard::decode::<Message>(&buf[..len + 1]),
+        Err(Error::TrailingBytes)
+    );
+}
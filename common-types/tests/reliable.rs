@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use common_types::command::Command;
+use common_types::frame;
+use common_types::message::{Message, MessageKind};
+use common_types::reliable::{Ack, Error, Event, Link, Sequenced, Transport};
+use common_types::{Batch, CentiCelsius, SensorMessage, Temperature, WireMessage};
+
+const TIMEOUT: u32 = 10;
+const RETRIES: u8 = 2;
+
+type Queue = Rc<RefCell<VecDeque<u8>>>;
+
+/// Error given by a [`Wire`] that fails writes
+#[derive(Debug, PartialEq)]
+struct Broken;
+
+/// One end of an in-memory wire, frames can be dropped on their way out or
+/// fail to be written
+struct Wire {
+    tx: Queue,
+    rx: Queue,
+    dropping: bool,
+    failing: bool,
+}
+
+impl Transport for Wire {
+    type Error = Broken;
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Broken> {
+        if self.failing {
+            return Err(Broken);
+        }
+        if !self.dropping {
+            self.tx.borrow_mut().extend(bytes);
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, Broken> {
+        Ok(self.rx.borrow_mut().pop_front())
+    }
+}
+
+fn pair() -> (Link<Wire, 4>, Link<Wire, 4>) {
+    let a: Queue = Default::default();
+    let b: Queue = Default::default();
+    let wire = |tx: &Queue, rx: &Queue| Wire {
+        tx: tx.clone(),
+        rx: rx.clone(),
+        dropping: false,
+        failing: false,
+    };
+    (
+        Link::new(wire(&a, &b), TIMEOUT, RETRIES),
+        Link::new(wire(&b, &a), TIMEOUT, RETRIES),
+    )
+}
+
+fn events(link: &mut Link<Wire, 4>, now: u32) -> Vec<Event> {
+    let mut events = Vec::new();
+    while let Some(event) = link.poll(now).unwrap() {
+        events.push(event);
+    }
+    events
+}
+
+fn command(level: u8) -> Message {
+    Message::Command(Command::SetBrightness(level))
+}
+
+#[test]
+fn delivers_and_acknowledges() {
+    let (mut a, mut b) = pair();
+
+    assert_eq!(a.send_reliable(command(1), 0), Ok(0));
+    assert_eq!(a.send_reliable(command(2), 0), Ok(1));
+    a.send(SensorMessage::Temperature(Temperature(20.0)))
+        .unwrap();
+
+    assert_eq!(
+        events(&mut b, 1),
+        [
+            Event::Received(command(1)),
+            Event::Received(command(2)),
+            Event::Received(Message::Sensor(SensorMessage::Temperature(Temperature(
+                20.0
+            )))),
+        ]
+    );
+    assert_eq!(
+        events(&mut a, 2),
+        [Event::Delivered(0), Event::Delivered(1)]
+    );
+
+    // Nothing is left to send again
+    assert_eq!(events(&mut a, 100), []);
+    assert_eq!(events(&mut b, 100), []);
+}
+
+#[test]
+fn resends_lost_messages() {
+    let (mut a, mut b) = pair();
+
+    a.transport_mut().dropping = true;
+    a.send_reliable(command(1), 0).unwrap();
+    a.transport_mut().dropping = false;
+    a.send_reliable(command(2), 0).unwrap();
+
+    // The second message arrives first, the first one is asked for again
+    assert_eq!(events(&mut b, 1), []);
+    assert_eq!(events(&mut a, 2), []);
+    assert_eq!(
+        events(&mut b, 3),
+        [Event::Received(command(1)), Event::Received(command(2))]
+    );
+    assert_eq!(
+        events(&mut a, 3),
+        [Event::Delivered(0), Event::Delivered(1)]
+    );
+
+    // The first acknowledgement is lost, the timeout sends the messages again
+    // and the duplicates are only acknowledged
+    b.transport_mut().dropping = true;
+    a.send_reliable(command(3), 4).unwrap();
+    assert_eq!(events(&mut b, 5), [Event::Received(command(3))]);
+    b.transport_mut().dropping = false;
+    assert_eq!(events(&mut a, 5), []);
+    assert_eq!(events(&mut a, 4 + TIMEOUT), []);
+    assert_eq!(events(&mut b, 15), []);
+    assert_eq!(events(&mut a, 16), [Event::Delivered(2)]);
+}
+
+#[test]
+fn delivers_messages_whose_acknowledgement_failed() {
+    let (mut a, mut b) = pair();
+
+    // The acknowledgement can't be written, so the message is only taken
+    // once it's sent again
+    a.send_reliable(command(1), 0).unwrap();
+    b.transport_mut().failing = true;
+    assert_eq!(b.poll(1), Err(Error::Transport(Broken)));
+    b.transport_mut().failing = false;
+    assert_eq!(events(&mut b, 1), []);
+    assert_eq!(events(&mut a, TIMEOUT), []);
+    assert_eq!(events(&mut b, TIMEOUT), [Event::Received(command(1))]);
+    assert_eq!(events(&mut a, TIMEOUT), [Event::Delivered(0)]);
+}
+
+#[test]
+fn gives_up_after_retries() {
+    let (mut a, mut b) = pair();
+
+    a.send_reliable(command(0), 0).unwrap();
+    assert_eq!(events(&mut b, 0), [Event::Received(command(0))]);
+    assert_eq!(events(&mut a, 0), [Event::Delivered(0)]);
+
+    a.transport_mut().dropping = true;
+    for level in 1..5 {
+        a.send_reliable(command(level), 0).unwrap();
+    }
+    assert_eq!(a.send_reliable(command(5), 0), Err(Error::WindowFull));
+
+    let mut now = 0;
+    for _ in 0..RETRIES {
+        now += TIMEOUT;
+        assert_eq!(events(&mut a, now), []);
+    }
+    now += TIMEOUT;
+    assert_eq!(
+        events(&mut a, now),
+        (1..5)
+            .map(|level| Event::Failed(command(level)))
+            .collect::<Vec<_>>()
+    );
+
+    // The peer stops waiting for the messages given up on
+    a.transport_mut().dropping = false;
+    assert_eq!(a.send_reliable(command(9), now), Ok(5));
+    assert_eq!(events(&mut b, now), [Event::Received(command(9))]);
+    assert_eq!(events(&mut a, now), [Event::Delivered(5)]);
+}
+
+#[test]
+fn rejects_messages_that_cant_be_sent() {
+    let (mut a, _) = pair();
+
+    let mut batch = Batch::new(MessageKind::TemperatureCenti, 0).unwrap();
+    while !batch.is_full() {
+        batch
+            .push(SensorMessage::TemperatureCenti(CentiCelsius(0)))
+            .unwrap();
+    }
+    assert_eq!(
+        a.send_reliable(batch, 0),
+        Err(Error::Frame(frame::Error::PayloadTooLarge))
+    );
+    assert!(matches!(
+        a.send_reliable(command(16), 0),
+        Err(Error::Invalid(_))
+    ));
+}
+
+#[test]
+fn sequenced_layout() {
+    let sequenced = Sequenced::new(7, 5, &command(3)).unwrap();
+    let mut buf = [0; Sequenced::SIZE];
+    let len = sequenced.encode_into(&mut buf).unwrap();
+    assert_eq!(&buf[..len], &[7, 5, MessageKind::Command.tag(), 0x02, 3]);
+    assert_eq!(Sequenced::decode(&buf[..len]), Ok(sequenced));
+    assert_eq!(sequenced.message(), Ok(command(3)));
+
+    let len = Ack::Nack(200).encode_into(&mut buf).unwrap();
+    assert_eq!(&buf[..len], &[1, 200]);
+}
@@ -0,0 +1,85 @@
+use common_types::message::Message;
+use common_types::sample::{SequenceStatus, SequenceTracker, Sequencer};
+use common_types::{Sample, SensorMessage, Temperature, WireMessage};
+
+#[test]
+fn sample_layout() {
+    let sample = Sample {
+        seq: 0x0102,
+        timestamp: 0x0304_0506,
+        value: SensorMessage::Temperature(Temperature(21.5)),
+    };
+    let mut buf = [0; Message::SIZE];
+    let len = sample.encode_into(&mut buf).unwrap();
+    let mut expected = vec![0x02, 0x01, 0x06, 0x05, 0x04, 0x03, 0x01];
+    expected.extend(21.5f32.to_le_bytes());
+    assert_eq!(&buf[..len], expected);
+    assert_eq!(Sample::decode(&buf[..len]), Ok(sample));
+}
+
+#[test]
+fn tracks_gaps_and_duplicates() {
+    let mut tracker = SequenceTracker::new();
+    assert_eq!(tracker.update(10, 100), SequenceStatus::First);
+    assert_eq!(tracker.update(11, 110), SequenceStatus::InOrder);
+    assert_eq!(tracker.update(15, 150), SequenceStatus::Gap { lost: 3 });
+    assert_eq!(tracker.update(15, 150), SequenceStatus::Duplicate);
+    // Arrived after a later sample
+    assert_eq!(tracker.update(13, 130), SequenceStatus::Duplicate);
+    assert_eq!(tracker.update(16, 160), SequenceStatus::InOrder);
+
+    assert_eq!(tracker.received(), 6);
+    assert_eq!(tracker.lost(), 3);
+    assert_eq!(tracker.duplicated(), 2);
+    // Lost samples count towards the period, duplicates don't
+    assert_eq!(tracker.period_ticks(), Some(10));
+    assert_eq!(tracker.rate_millihertz(1_000), Some(100_000));
+
+    tracker.reset();
+    assert_eq!(tracker.received(), 0);
+    assert_eq!(tracker.period_ticks(), None);
+    assert_eq!(tracker.update(3, 0), SequenceStatus::First);
+}
+
+#[test]
+fn sequence_numbers_and_timestamps_wrap_around() {
+    let mut sequencer = Sequencer::new();
+    for seq in 0..=u16::MAX {
+        assert_eq!(sequencer.stamp(0, ()).seq, seq);
+    }
+    assert_eq!(sequencer.stamp(0, ()).seq, 0);
+
+    let mut timestamp = u32::MAX - 5;
+    let mut tracker = SequenceTracker::new();
+    assert_eq!(
+        tracker.update(u16::MAX - 1, timestamp),
+        SequenceStatus::First
+    );
+    for seq in [u16::MAX, 0, 1] {
+        timestamp = timestamp.wrapping_add(4);
+        assert_eq!(tracker.update(seq, timestamp), SequenceStatus::InOrder);
+    }
+    timestamp = timestamp.wrapping_add(12);
+    assert_eq!(
+        tracker.update(4, timestamp),
+        SequenceStatus::Gap { lost: 2 }
+    );
+    assert_eq!(tracker.update(u16::MAX, 0), SequenceStatus::Duplicate);
+    assert_eq!(tracker.period_ticks(), Some(4));
+
+    // Up to half the range ahead is a gap, further is an old sample
+    let mut tracker = SequenceTracker::new();
+    tracker.update(u16::MAX - 10, 0);
+    assert_eq!(
+        tracker.update((u16::MAX - 10).wrapping_add(u16::MAX / 2), 0),
+        SequenceStatus::Gap {
+            lost: u16::MAX / 2 - 1
+        }
+    );
+    let mut tracker = SequenceTracker::new();
+    tracker.update(5, 0);
+    assert_eq!(
+        tracker.update(5u16.wrapping_add(u16::MAX / 2 + 1), 0),
+        SequenceStatus::Duplicate
+    );
+}
@@ -0,0 +1,74 @@
+use common_types::message::{Error, Message, MessageKind};
+use common_types::stats::{Accumulator, Channel, Stats};
+use common_types::validate::ValidationError;
+use common_types::{SensorMessage, WireMessage};
+
+#[test]
+fn summarizes_a_window() {
+    let mut acc = Accumulator::new(Channel::AccelerationZ);
+    assert_eq!(acc.stats(), None);
+
+    let reading = |z| SensorMessage::Acceleration { x: 0.0, y: 1.0, z };
+    for (i, z) in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+        .into_iter()
+        .enumerate()
+    {
+        for (channel, value) in Channel::values(&reading(z)) {
+            if channel == acc.channel() {
+                acc.push(100 + i as u32, value);
+            }
+        }
+    }
+    acc.push(200, f32::NAN);
+
+    let stats = acc.take().unwrap();
+    assert_eq!(
+        stats,
+        Stats {
+            channel: Channel::AccelerationZ,
+            timestamp: 100,
+            count: 8,
+            min: 2.0,
+            max: 9.0,
+            mean: 5.0,
+            variance: 4.0,
+        }
+    );
+    assert!(acc.is_empty());
+
+    // Large values that barely vary keep their variance
+    for i in 0..1000 {
+        acc.push(i, 9.81 + if i % 2 == 0 { 0.01 } else { -0.01 });
+    }
+    let variance = acc.stats().unwrap().variance;
+    assert!((variance - 1e-4).abs() < 1e-6, "{variance}");
+}
+
+#[test]
+fn stats_layout() {
+    let stats = Stats {
+        channel: Channel::Temperature,
+        timestamp: 1,
+        count: 2,
+        min: 1.0,
+        max: 2.0,
+        mean: 1.5,
+        variance: 0.25,
+    };
+    let mut buf = [0; Message::SIZE];
+    let len = Message::from(stats).encode_into(&mut buf).unwrap();
+    let mut expected = vec![MessageKind::Stats.tag(), 0, 1, 0, 0, 0, 2, 0, 0, 0];
+    for value in [1.0f32, 2.0, 1.5, 0.25] {
+        expected.extend(value.to_le_bytes());
+    }
+    assert_eq!(&buf[..len], &expected[..]);
+    assert_eq!(Message::decode(&buf[..len]), Ok(Message::Stats(stats)));
+
+    // A mean outside of the range can't be right
+    let bad = Stats { mean: 3.0, ..stats };
+    let len = bad.encode_into(&mut buf).unwrap();
+    assert_eq!(
+        Stats::decode(&buf[..len]),
+        Err(Error::Invalid(ValidationError::OutOfRange))
+    );
+}
@@ -0,0 +1,63 @@
+use common_types::frame::Decoder;
+use common_types::message::{self, Message};
+use common_types::status::{DeviceStatus, Faults, ResetReason};
+use common_types::validate::ValidationError;
+use common_types::WireMessage;
+
+fn status() -> DeviceStatus {
+    DeviceStatus {
+        uptime: 3600,
+        reset_reason: ResetReason::Pin,
+        faults: Faults::DISPLAY_INIT,
+        sensor_errors: 2,
+        display_errors: 0,
+        display_retries: 0x0102,
+        link_errors: 3,
+    }
+}
+
+#[test]
+fn status_layout() {
+    let mut buf = [0; DeviceStatus::SIZE];
+    let len = status().encode_into(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..len],
+        &[0x10, 0x0E, 0, 0, 2, 0b10, 2, 0, 0, 0, 0x02, 0x01, 3, 0]
+    );
+    assert_eq!(DeviceStatus::decode(&buf[..len]), Ok(status()));
+
+    buf[4] = 6;
+    assert_eq!(
+        DeviceStatus::decode(&buf[..len]),
+        Err(message::Error::Invalid(ValidationError::UnknownVariant))
+    );
+
+    let mut frame = [0; 64];
+    let len = Message::from(status()).to_frame(&mut frame).unwrap();
+    let mut decoder = Decoder::new();
+    let decoded = frame[..len].iter().find_map(|&b| decoder.push(b).unwrap());
+    assert_eq!(decoded, Some(Message::DeviceStatus(status())));
+}
+
+#[test]
+fn reports_new_problems() {
+    let previous = status();
+    assert!(previous.is_faulty());
+    assert!(!DeviceStatus::new(ResetReason::PowerOn).is_faulty());
+    assert!(DeviceStatus::new(ResetReason::Watchdog).is_faulty());
+
+    let mut current = DeviceStatus {
+        uptime: 3605,
+        ..previous
+    };
+    assert!(!current.is_worse_than(&previous));
+    current.sensor_errors += 1;
+    assert!(current.is_worse_than(&previous));
+    current.sensor_errors -= 1;
+    current.link_errors += 1;
+    assert!(current.is_worse_than(&previous));
+
+    // Counters start over when the device restarts
+    let restarted = DeviceStatus::new(ResetReason::Software);
+    assert!(restarted.is_worse_than(&previous));
+}
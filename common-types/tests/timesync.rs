@@ -0,0 +1,73 @@
+use common_types::message::Message;
+use common_types::timesync::{ClockSync, TimeReply, TimeRequest};
+use common_types::WireMessage;
+
+const LOCAL_HZ: u32 = 1_000_000;
+const REMOTE_HZ: u32 = 48_000_000;
+
+/// Remote clock running 100 ppm fast, started 1234 s before the local one
+fn remote_at(local: u64) -> u32 {
+    let ticks = local * (REMOTE_HZ as u64 + 4_800) / LOCAL_HZ as u64;
+    (ticks + 1234 * REMOTE_HZ as u64) as u32
+}
+
+/// Exchange starting at local tick count `sent`, each way taking `delay` µs
+fn exchange(clock: &mut ClockSync, sent: u64, delay: u64) -> Option<u32> {
+    let request = clock.request(sent as u32);
+    let receive = remote_at(sent + delay);
+    let transmit = remote_at(sent + delay + 50);
+    let reply = TimeReply::new(&request, receive, transmit, REMOTE_HZ);
+    clock.update(&reply, (sent + 2 * delay + 50) as u32)
+}
+
+#[test]
+fn maps_remote_timestamps() {
+    let mut clock = ClockSync::new(LOCAL_HZ, 10_000);
+    assert_eq!(clock.to_local(0), None);
+
+    let round_trip = exchange(&mut clock, 1_000_000, 2_000).unwrap();
+    assert!(round_trip.abs_diff(4_000) <= 1, "{round_trip}");
+    // Close even before the drift is known
+    let local = clock.to_local(remote_at(1_500_000)).unwrap();
+    assert!(local.abs_diff(1_500_000) < 100, "{local}");
+
+    for second in 2..60 {
+        exchange(&mut clock, second * 1_000_000, 2_000).unwrap();
+    }
+    let drift = clock.drift_ppm().unwrap();
+    assert!((95..=105).contains(&drift), "{drift}");
+
+    // Readings taken before and after the last exchange
+    for local in [58_000_000, 59_500_000, 61_000_000] {
+        let mapped = clock.to_local(remote_at(local)).unwrap();
+        assert!(mapped.abs_diff(local as u32) <= 2, "{local} {mapped}");
+    }
+}
+
+#[test]
+fn ignores_slow_exchanges() {
+    let mut clock = ClockSync::new(LOCAL_HZ, 10_000);
+    assert_eq!(exchange(&mut clock, 0, 6_000), None);
+    assert_eq!(clock.to_local(0), None);
+
+    // Tick count wrapping in between
+    let wrap = 1 << 32;
+    assert!(exchange(&mut clock, wrap - 1_000, 1_000).is_some());
+    let local = clock.to_local(remote_at(wrap + 5_000)).unwrap();
+    assert!(local.abs_diff(5_000) < 100, "{local}");
+}
+
+#[test]
+fn reply_layout() {
+    let reply = TimeReply::new(&TimeRequest { origin: 1 }, 2, 3, 4);
+    let mut buf = [0; Message::SIZE];
+    let len = Message::from(reply).encode_into(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..len],
+        &[0x10, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0]
+    );
+    assert_eq!(Message::decode(&buf[..len]), Ok(Message::TimeReply(reply)));
+
+    buf[13] = 0;
+    assert!(Message::decode(&buf[..len]).is_err());
+}
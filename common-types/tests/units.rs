@@ -0,0 +1,91 @@
+use common_types::{Celsius, CentiCelsius, Fahrenheit, Kelvin, Temperature};
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+#[test]
+fn conversions() {
+    assert_eq!(Fahrenheit::from(Celsius(100.0)), Fahrenheit(212.0));
+    assert_eq!(Celsius::from(Fahrenheit(-40.0)), Celsius(-40.0));
+    assert_eq!(Kelvin::from(Celsius(0.0)), Kelvin(273.15));
+    assert_eq!(Celsius::from(Kelvin(0.0)), Celsius(-273.15));
+    assert!(close(Fahrenheit::from(Kelvin(0.0)).0, -459.67));
+    assert!(close(Kelvin::from(Fahrenheit(32.0)).0, 273.15));
+
+    for c in [-40.0, -9.99, 0.0, 21.5, 36.6, 85.0] {
+        let back = Celsius::from(Kelvin::from(Fahrenheit::from(Celsius(c))));
+        assert!(close(back.0, c), "{} came back as {}", c, back.0);
+    }
+
+    assert_eq!(Celsius::from(CentiCelsius(-1234)), Celsius(-12.34));
+    assert_eq!(Temperature(36.6).celsius(), Celsius(36.6));
+    assert_eq!(Temperature::from(Celsius(20.0)), Temperature(20.0));
+}
+
+#[test]
+fn comparisons_stay_in_one_unit() {
+    assert!(Celsius(-9.99) < Celsius(0.0));
+    assert!(Kelvin(0.0) < Kelvin(0.1));
+    assert_eq!(Fahrenheit(50.0), Fahrenheit(50.0));
+
+    // Comparing across units takes an explicit conversion, which gives the
+    // same answer from either side
+    let c = Celsius(36.6);
+    let f = Fahrenheit::from(c);
+    assert_eq!(
+        Celsius::from(f) < Celsius(37.0),
+        f < Fahrenheit::from(Celsius(37.0))
+    );
+    assert!(Celsius(f32::NAN).partial_cmp(&Celsius(0.0)).is_none());
+}
+
+#[test]
+fn display() {
+    assert_eq!(Celsius(21.5).to_string(), "21.5 °C");
+    assert_eq!(format!("{:.2}", Celsius(21.5)), "21.50 °C");
+    assert_eq!(format!("{:.1}", Fahrenheit(70.66)), "70.7 °F");
+    assert_eq!(format!("{:.0}", Kelvin(273.15)), "273 K");
+}
+
+#[test]
+fn rounds_to_the_nearest_hundredth() {
+    let centi = |c: f32| CentiCelsius::from(Temperature(c)).0;
+
+    // Halves go away from zero on both sides
+    assert_eq!(centi(0.125), 13);
+    assert_eq!(centi(-0.125), -13);
+    assert_eq!(centi(21.375), 2138);
+    assert_eq!(centi(-21.375), -2138);
+    assert_eq!(centi(0.004), 0);
+    assert_eq!(centi(-0.004), 0);
+
+    // The sensor's limits, and readings that round onto them
+    assert_eq!(centi(85.0), CentiCelsius::MAX.0);
+    assert_eq!(centi(84.996), CentiCelsius::MAX.0);
+    assert_eq!(centi(-40.0), CentiCelsius::MIN.0);
+    assert_eq!(centi(-39.996), CentiCelsius::MIN.0);
+    assert_eq!(centi(84.994), 8499);
+    assert_eq!(centi(-39.994), -3999);
+
+    // Values that don't fit saturate, NaN has nothing to round to
+    assert_eq!(centi(400.0), i16::MAX);
+    assert_eq!(centi(-400.0), i16::MIN);
+    assert_eq!(centi(f32::INFINITY), i16::MAX);
+    assert_eq!(centi(f32::NEG_INFINITY), i16::MIN);
+    assert_eq!(centi(f32::NAN), 0);
+}
+
+#[test]
+fn splits_hundredths_of_a_degree() {
+    let parts = |centi: i16| {
+        let temp = CentiCelsius(centi);
+        (temp.degrees(), temp.hundredths())
+    };
+    assert_eq!(parts(2150), (21, 50));
+    assert_eq!(parts(8500), (85, 0));
+    // Both parts carry the sign
+    assert_eq!(parts(-1234), (-12, -34));
+    assert_eq!(parts(-5), (0, -5));
+    assert_eq!(parts(-4000), (-40, 0));
+}
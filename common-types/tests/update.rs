@@ -0,0 +1,191 @@
+use std::convert::Infallible;
+
+use common_types::message::{Message, MessageKind};
+use common_types::update::{
+    self, Receiver, Sender, Storage, UpdateBegin, UpdateChunk, UpdateCommit, UpdateReply,
+    UpdateStatus, CHUNK_SIZE,
+};
+use common_types::WireMessage;
+
+/// Storage in memory, remembering whether the image was committed
+#[derive(Default)]
+struct Memory {
+    image: Vec<u8>,
+    committed: bool,
+}
+
+impl Storage for Memory {
+    type Error = Infallible;
+
+    fn capacity(&self) -> u32 {
+        4096
+    }
+
+    fn begin(&mut self, size: u32) -> Result<(), Infallible> {
+        self.image = vec![0xFF; size as usize];
+        self.committed = false;
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Infallible> {
+        let offset = offset as usize;
+        self.image[offset..offset + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), Infallible> {
+        self.committed = true;
+        Ok(())
+    }
+}
+
+fn image(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 7) as u8).collect()
+}
+
+/// Goes through an encoded frame payload, like it would over the link
+fn relay(message: Message) -> Message {
+    let mut buf = [0; Message::SIZE];
+    let len = message.encode_into(&mut buf).unwrap();
+    Message::decode(&buf[..len]).unwrap()
+}
+
+#[test]
+fn hash_check_value() {
+    let expected = [
+        0xBA, 0x78, 0x16, 0xBF, 0x8F, 0x01, 0xCF, 0xEA, 0x41, 0x41, 0x40, 0xDE, 0x5D, 0xAE, 0x22,
+        0x23, 0xB0, 0x03, 0x61, 0xA3, 0x96, 0x17, 0x7A, 0x9C, 0xB4, 0x10, 0xFF, 0x61, 0xF2, 0x00,
+        0x15, 0xAD,
+    ];
+    assert_eq!(update::hash(b"abc"), expected);
+}
+
+#[test]
+fn sends_and_resumes() {
+    let image = image(3 * CHUNK_SIZE + 10);
+    let mut receiver = Receiver::new(Memory::default());
+
+    // The link drops after the second chunk
+    let mut sender = Sender::new(&image).unwrap();
+    let mut message = sender.message();
+    for _ in 0..3 {
+        let reply = receiver.handle(&relay(message)).unwrap();
+        message = sender.handle(&reply).unwrap().unwrap();
+    }
+    assert_eq!(sender.progress(), 2 * CHUNK_SIZE as u32);
+
+    // Beginning again picks up where it stopped
+    let mut sender = Sender::new(&image).unwrap();
+    let reply = receiver.handle(&relay(sender.message())).unwrap();
+    assert_eq!(reply, UpdateReply::new(UpdateStatus::Ready, 128));
+
+    // The same chunk sent twice is only written once
+    let chunk = sender.handle(&reply).unwrap().unwrap();
+    receiver.handle(&chunk).unwrap();
+    let reply = receiver.handle(&chunk).unwrap();
+    assert_eq!(reply, UpdateReply::new(UpdateStatus::Ready, 192));
+
+    let mut message = sender.handle(&reply).unwrap();
+    while let Some(next) = message {
+        let reply = receiver.handle(&relay(next)).unwrap();
+        message = sender.handle(&reply).unwrap();
+    }
+    assert!(sender.is_done());
+    assert!(receiver.storage().committed);
+    assert_eq!(receiver.storage().image, image);
+}
+
+#[test]
+fn rejects_corrupted_data() {
+    let image = image(100);
+    let mut receiver = Receiver::new(Memory::default());
+    let mut sender = Sender::new(&image).unwrap();
+
+    let reply = receiver.handle(&sender.message()).unwrap();
+    let Some(Message::UpdateChunk(mut chunk)) = sender.handle(&reply).unwrap() else {
+        panic!("expected a chunk");
+    };
+    chunk.crc ^= 1;
+    let reply = receiver.handle(&chunk.into()).unwrap();
+    assert_eq!(reply, UpdateReply::new(UpdateStatus::Corrupt, 0));
+    assert!(matches!(
+        sender.handle(&reply),
+        Ok(Some(Message::UpdateChunk(chunk))) if chunk.seq == 0 && chunk.is_intact()
+    ));
+
+    // An image that doesn't match the announced hash is never committed
+    let mut hash = update::hash(&image);
+    hash[31] ^= 1;
+    let begin = UpdateBegin { size: 100, hash };
+    receiver.handle(&begin.into()).unwrap();
+    for (seq, data) in image.chunks(CHUNK_SIZE).enumerate() {
+        let chunk = UpdateChunk::new(seq as u16, data).unwrap();
+        receiver.handle(&chunk.into()).unwrap();
+    }
+    let commit = Message::UpdateCommit(UpdateCommit { hash });
+    assert_eq!(
+        receiver.handle(&commit),
+        Some(UpdateReply::new(UpdateStatus::Mismatch, 0))
+    );
+    assert!(!receiver.storage().committed);
+}
+
+#[test]
+fn chunk_layout() {
+    let chunk = UpdateChunk::new(0x0102, &[0x31, 0x32, 0x33]).unwrap();
+    let mut buf = [0; Message::SIZE];
+    let len = Message::from(chunk).encode_into(&mut buf).unwrap();
+    let crc = 0x5BCEu16.to_le_bytes();
+    assert_eq!(
+        &buf[..len],
+        &[
+            MessageKind::UpdateChunk.tag(),
+            0x02,
+            0x01,
+            crc[0],
+            crc[1],
+            0x31,
+            0x32,
+            0x33
+        ]
+    );
+    assert_eq!(chunk.offset(), 0x0102 * CHUNK_SIZE as u32);
+    assert!(UpdateChunk::new(0, &[]).is_none());
+    assert!(UpdateChunk::new(0, &[0; CHUNK_SIZE + 1]).is_none());
+}
+
+#[test]
+fn commit_sent_again_is_answered_again() {
+    let image = image(CHUNK_SIZE);
+    let mut receiver = Receiver::new(Memory::default());
+    let mut sender = Sender::new(&image).unwrap();
+
+    let mut message = Some(sender.message());
+    let mut commit = None;
+    while let Some(next) = message {
+        let reply = receiver.handle(&next).unwrap();
+        if let Message::UpdateCommit(_) = next {
+            commit = Some(next);
+            // The reply to the commit is lost
+            break;
+        }
+        message = sender.handle(&reply).unwrap();
+    }
+
+    // The commit sent again is acknowledged instead of starting over
+    let commit = commit.unwrap();
+    assert_eq!(sender.message(), commit);
+    let reply = receiver.handle(&commit).unwrap();
+    assert_eq!(reply, UpdateReply::new(UpdateStatus::Committed, 64));
+    assert_eq!(sender.handle(&reply), Ok(None));
+    assert!(sender.is_done());
+
+    // Only for the image that was committed
+    let other = Message::UpdateCommit(UpdateCommit {
+        hash: update::hash(b"other"),
+    });
+    assert_eq!(
+        receiver.handle(&other),
+        Some(UpdateReply::new(UpdateStatus::Idle, 0))
+    );
+}
@@ -0,0 +1,113 @@
+use common_types::message::{Error, Message, MessageKind};
+use common_types::sensor::{ACCELERATION_RANGE, ANGULAR_RATE_RANGE, TEMPERATURE_RANGE};
+use common_types::validate::ValidationError;
+use common_types::{Sample, SensorMessage, Temperature, WireMessage};
+
+/// Decodes a message of the given kind from its payload
+fn decode(kind: MessageKind, payload: &[u8]) -> Result<Message, Error> {
+    Message::decode(&[&[kind.tag()][..], payload].concat())
+}
+
+fn axes(x: f32, y: f32, z: f32) -> Vec<u8> {
+    [x, y, z].iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Closest values outside of `range`, below and above it
+fn just_outside(range: &std::ops::RangeInclusive<f32>) -> [f32; 2] {
+    let below = f32::from_bits(range.start().to_bits() + 1);
+    let above = f32::from_bits(range.end().to_bits() + 1);
+    [below, above]
+}
+
+#[test]
+fn rejects_readings_that_are_not_finite() {
+    for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        let not_finite = Err(Error::Invalid(ValidationError::NotFinite));
+        assert_eq!(
+            decode(MessageKind::Temperature, &value.to_le_bytes()),
+            not_finite
+        );
+        assert_eq!(
+            decode(MessageKind::Acceleration, &axes(0.0, value, 9.8)),
+            not_finite
+        );
+        assert_eq!(
+            decode(MessageKind::AngularRate, &axes(0.0, 0.0, value)),
+            not_finite
+        );
+    }
+}
+
+#[test]
+fn rejects_readings_out_of_range() {
+    let out_of_range = Err(Error::Invalid(ValidationError::OutOfRange));
+
+    // The limits themselves are readings the sensor can produce
+    for value in [*TEMPERATURE_RANGE.start(), *TEMPERATURE_RANGE.end()] {
+        let decoded = decode(MessageKind::Temperature, &value.to_le_bytes());
+        assert_eq!(
+            decoded,
+            Ok(SensorMessage::Temperature(Temperature(value)).into())
+        );
+    }
+    for value in just_outside(&TEMPERATURE_RANGE) {
+        let decoded = decode(MessageKind::Temperature, &value.to_le_bytes());
+        assert_eq!(decoded, out_of_range, "{}", value);
+    }
+
+    for value in [*ACCELERATION_RANGE.start(), *ACCELERATION_RANGE.end()] {
+        assert!(decode(MessageKind::Acceleration, &axes(value, 0.0, 0.0)).is_ok());
+    }
+    for value in just_outside(&ACCELERATION_RANGE) {
+        let decoded = decode(MessageKind::Acceleration, &axes(0.0, 0.0, value));
+        assert_eq!(decoded, out_of_range, "{}", value);
+    }
+
+    for value in [*ANGULAR_RATE_RANGE.start(), *ANGULAR_RATE_RANGE.end()] {
+        assert!(decode(MessageKind::AngularRate, &axes(0.0, value, 0.0)).is_ok());
+    }
+    for value in just_outside(&ANGULAR_RATE_RANGE) {
+        let decoded = decode(MessageKind::AngularRate, &axes(value, 0.0, 0.0));
+        assert_eq!(decoded, out_of_range, "{}", value);
+    }
+
+    // Hundredths of a degree are held to the same range
+    let decoded = decode(MessageKind::TemperatureCenti, &8501i16.to_le_bytes());
+    assert_eq!(decoded, out_of_range);
+    let decoded = decode(MessageKind::TemperatureCenti, &(-4001i16).to_le_bytes());
+    assert_eq!(decoded, out_of_range);
+}
+
+#[test]
+fn rejects_readings_inside_other_messages() {
+    let sample = Sample {
+        seq: 1,
+        timestamp: 2,
+        value: SensorMessage::Temperature(Temperature(20.0)),
+    };
+    let mut bytes = [0; Message::SIZE];
+    let len = Message::from(sample).encode_into(&mut bytes).unwrap();
+    assert_eq!(Message::decode(&bytes[..len]), Ok(sample.into()));
+
+    bytes[len - 4..len].copy_from_slice(&f32::NAN.to_le_bytes());
+    assert_eq!(
+        Message::decode(&bytes[..len]),
+        Err(Error::Invalid(ValidationError::NotFinite))
+    );
+    bytes[len - 4..len].copy_from_slice(&100.0f32.to_le_bytes());
+    assert_eq!(
+        Message::decode(&bytes[..len]),
+        Err(Error::Invalid(ValidationError::OutOfRange))
+    );
+}
+
+#[test]
+fn rejects_unknown_variants() {
+    let unknown = Err(Error::Invalid(ValidationError::UnknownVariant));
+
+    // Command opcode, accelerometer rate and response status
+    assert_eq!(decode(MessageKind::Command, &[0x7F]), unknown);
+    assert_eq!(decode(MessageKind::Command, &[0x01, 7]), unknown);
+    assert_eq!(decode(MessageKind::Response, &[0x01, 4]), unknown);
+    assert_eq!(decode(MessageKind::Response, &[0x00, 0]), unknown);
+}
@@ -3,15 +3,55 @@
 #![feature(exclusive_range_pattern)]
 
 use adafruit_7segment::{Index, SevenSegment};
+use common_types::command::{AccelOdr, Command, Response, Status};
+use common_types::descriptor::{BoardType, Descriptor, OdrSet, SensorPart};
+use common_types::heartbeat::{HeartbeatTimer, LinkMonitor, LinkState};
+use common_types::hello::{Hello, KindSet};
+use common_types::message::{Message, MessageKind};
+use common_types::reliable::{self, Event, Link, Transport};
+use common_types::sample::Sequencer;
+use common_types::stats::{Accumulator, Channel};
+use common_types::status::{DeviceStatus, Faults, ResetReason};
+use common_types::timesync::TimeReply;
+use common_types::update::{UpdateReply, UpdateStatus};
+use common_types::{Batch, Celsius, CentiCelsius, SensorMessage, Temperature};
+use cortex_m::peripheral::DWT;
 use ht16k33::{Dimming, Display, HT16K33};
 use ism330dhcx::ctrl1xl::Odr_Xl;
+use ism330dhcx::ctrl2g::Odr;
 use ism330dhcx::Ism330Dhcx;
+use nb::block;
 use nucleo::hal::delay::Delay;
+use nucleo::hal::i2c::{self, I2c};
 use nucleo::hal::prelude::*;
+use nucleo::hal::serial::{self, Rx, Tx};
+use nucleo::pac::{I2C1, RCC, USART6};
 use nucleo_h7xx as nucleo;
 
 const DISP_I2C_ADDR: u8 = 0x70;
 
+/// Address of the 96 bit unique ID programmed into every STM32H7 at the
+/// factory
+const UID: *const [u8; 12] = 0x1FF1_E800 as *const [u8; 12];
+
+/// Messages sent reliably that can wait for an acknowledgement at once
+const WINDOW: usize = 4;
+/// Cycles to wait for the Raspberry Pi to acknowledge a message, about half a
+/// second
+const RETRANSMIT_TIMEOUT: u32 = 240_000_000;
+/// Times a message is sent again before giving up on it
+const MAX_RETRIES: u8 = 5;
+
+/// Seconds between status reports to the Raspberry Pi
+const STATUS_PERIOD: u32 = 5;
+
+/// Milliseconds between heartbeats sent to the Raspberry Pi
+const HEARTBEAT_PERIOD: u32 = 1000;
+/// Milliseconds without hearing from the Raspberry Pi before the link is
+/// considered degraded, then lost
+const LINK_DEGRADED_AFTER: u32 = 2500;
+const LINK_LOST_AFTER: u32 = 5000;
+
 #[cortex_m_rt::entry]
 fn main() -> ! {
     // - board setup ----------------------------------------------------------
@@ -20,7 +60,14 @@ fn main() -> ! {
 
     let dp = nucleo::pac::Peripherals::take().unwrap();
 
-    let core = nucleo::pac::CorePeripherals::take().unwrap();
+    let mut core = nucleo::pac::CorePeripherals::take().unwrap();
+
+    // The cycle counter timestamps samples sent to the Raspberry Pi
+    core.DCB.enable_trace();
+    core.DWT.enable_cycle_counter();
+
+    // The reset flags have to be read before the clocks take over the RCC
+    let mut health = DeviceStatus::new(reset_reason(&dp.RCC));
 
     let ccdr = board.freeze_clocks(dp.PWR.constrain(), dp.RCC.constrain(), &dp.SYSCFG);
 
@@ -44,12 +91,16 @@ fn main() -> ! {
         .I2C1
         .i2c((scl, sda), 100.kHz(), ccdr.peripheral.I2C1, &ccdr.clocks);
 
-    let mut sensor = Ism330Dhcx::new(&mut i2c1).unwrap();
-
-    sensor
-        .ctrl1xl
-        .set_accelerometer_data_rate(&mut i2c1, Odr_Xl::Hz52)
-        .expect("Don't know why setting data rate could fail");
+    // Without a sensor there's nothing to send, but the Raspberry Pi still
+    // gets told why
+    let mut sensor = match init_sensor(&mut i2c1) {
+        Ok(sensor) => Some(sensor),
+        Err(e) => {
+            defmt::debug!("Failed to set up the sensor: {:?}", defmt::Debug2Format(&e));
+            health.faults = health.faults.with(Faults::SENSOR_INIT);
+            None
+        }
+    };
 
     // Configure the SCL and the SDA pin for display I2C bus
     let scl = pins.d69.into_alternate_open_drain();
@@ -59,35 +110,288 @@ fn main() -> ! {
         .I2C4
         .i2c((scl, sda), 100.kHz(), ccdr.peripheral.I2C4, &ccdr.clocks);
 
+    // Configure the TX and the RX pin for the UART link with the Raspberry Pi
+    let tx = pins.d1.into_alternate::<7>();
+    let rx = pins.d0.into_alternate::<7>();
+
+    let (tx, rx) = dp
+        .USART6
+        .serial((tx, rx), 9600.bps(), ccdr.peripheral.USART6, &ccdr.clocks)
+        .expect("Failed to initialize USART6")
+        .split();
+
+    // Timeouts are measured with the cycle counter
+    let mut link: Link<Uart, WINDOW> = Link::new(Uart { tx, rx }, RETRANSMIT_TIMEOUT, MAX_RETRIES);
+    // Each kind of reading is numbered separately, so that the timestamps of
+    // every sequence only go forward even though they're sent in batches
+    let mut sequencers = [Sequencer::new(), Sequencer::new(), Sequencer::new()];
+    let mut batches: [Option<Batch>; 3] = [None; 3];
+    let mut streaming = true;
+    // Milliseconds between summaries, readings are sent as they come while 0
+    let mut summary_period = 0;
+    let mut summaries = Channel::ALL.map(Accumulator::new);
+    let mut window_start = 0;
+    // Messages the Raspberry Pi understands, assumed to be all of them until
+    // its hello says otherwise
+    let mut usable = KindSet::all();
+
+    let hello = Hello::new(common_types::firmware_version!());
+    send(&mut link, &mut health, hello);
+
     let mut ht16k33 = HT16K33::new(i2c4, DISP_I2C_ADDR);
-    ht16k33.initialize().expect("Failed to initialize ht16k33");
-    ht16k33
-        .set_display(Display::ON)
-        .expect("Could not turn on the display!");
-    ht16k33
-        .set_dimming(Dimming::BRIGHTNESS_MAX)
-        .expect("Could not set dimming!");
+    let display = ht16k33
+        .initialize()
+        .and_then(|_| ht16k33.set_display(Display::ON))
+        .and_then(|_| ht16k33.set_dimming(Dimming::BRIGHTNESS_MAX));
+    if let Err(e) = &display {
+        defmt::debug!("Failed to set up the display: {:?}", e);
+        health.faults = health.faults.with(Faults::DISPLAY_INIT);
+    }
+
+    // Tells the Raspberry Pi which board this is, when it asks
+    let descriptor = Descriptor {
+        // NOTE(unsafe) The ID is read only and always readable
+        unique_id: unsafe { core::ptr::read_volatile(UID) },
+        board: BoardType::NucleoH745ZiQ,
+        sensor: match sensor {
+            Some(_) => SensorPart::Ism330dhcx,
+            None => SensorPart::Absent,
+        },
+        accel_odrs: match sensor {
+            Some(_) => OdrSet::all(),
+            None => OdrSet::EMPTY,
+        },
+        display: display.is_ok(),
+    };
+
+    // Cycles since boot, the cycle counter alone wraps around in seconds
+    let core_clock = u64::from(ccdr.clocks.c_ck().raw());
+    let mut cycles = 0u64;
+    let mut last_count = DWT::cycle_count();
+    let mut next_report = 0;
+
+    let mut heartbeats = HeartbeatTimer::new(HEARTBEAT_PERIOD);
+    let mut monitor = LinkMonitor::new(LINK_DEGRADED_AFTER, LINK_LOST_AFTER);
+    // Kept until the sensor can be read, whatever is being streamed
+    let mut sample_requested = false;
 
     loop {
-        let temp = sensor.get_temperature(&mut i2c1).unwrap();
-        // Formatting a float using the whole display
+        let count = DWT::cycle_count();
+        cycles += u64::from(count.wrapping_sub(last_count));
+        last_count = count;
+        // Milliseconds since boot
+        let now = (cycles * 1000 / core_clock) as u32;
+
+        loop {
+            let event = match link.poll(DWT::cycle_count()) {
+                Ok(Some(event)) => event,
+                Ok(None) => break,
+                Err(reliable::Error::Decode(e)) => {
+                    defmt::debug!("Bad frame from Raspberry Pi: {:?}", defmt::Debug2Format(&e));
+                    continue;
+                }
+                Err(e) => {
+                    defmt::debug!("UART link failed: {:?}", defmt::Debug2Format(&e));
+                    break;
+                }
+            };
+
+            if let Event::Received(_) = event {
+                monitor.received(now);
+            }
+
+            match event {
+                // Answer the Raspberry Pi's handshake, it may have booted after us
+                Event::Received(Message::Hello(remote)) => {
+                    usable = hello.check(&remote).usable();
+                    send(&mut link, &mut health, hello);
+                }
+                Event::Received(Message::Command(command)) => {
+                    let status = match command {
+                        Command::SetAccelOdr(odr) => match sensor.as_mut().map(|sensor| {
+                            sensor
+                                .ctrl1xl
+                                .set_accelerometer_data_rate(&mut i2c1, accel_odr(odr))
+                        }) {
+                            Some(Ok(_)) => Status::Ack,
+                            Some(Err(_)) => {
+                                health.sensor_errors = health.sensor_errors.saturating_add(1);
+                                Status::Failed
+                            }
+                            None => Status::Failed,
+                        },
+                        Command::SetBrightness(level) => match Dimming::from_u8(level) {
+                            Ok(dimming) => match ht16k33.set_dimming(dimming) {
+                                Ok(_) => Status::Ack,
+                                Err(_) => {
+                                    health.display_errors = health.display_errors.saturating_add(1);
+                                    Status::Failed
+                                }
+                            },
+                            Err(_) => Status::InvalidArgument,
+                        },
+                        Command::RequestSample => {
+                            sample_requested = true;
+                            Status::Ack
+                        }
+                        Command::StartStreaming => {
+                            streaming = true;
+                            Status::Ack
+                        }
+                        Command::StopStreaming => {
+                            streaming = false;
+                            batches = [None; 3];
+                            summaries = Channel::ALL.map(Accumulator::new);
+                            Status::Ack
+                        }
+                        Command::RequestDescriptor => Status::Ack,
+                        Command::SetSummaryPeriod(seconds) => {
+                            summary_period = u32::from(seconds) * 1000;
+                            batches = [None; 3];
+                            summaries = Channel::ALL.map(Accumulator::new);
+                            window_start = now;
+                            Status::Ack
+                        }
+                    };
+                    let response = Response::new(&command, status);
+                    send(&mut link, &mut health, response);
+                    if command == Command::RequestDescriptor {
+                        send(&mut link, &mut health, descriptor);
+                    }
+                }
+                // Timestamps are in cycles, like the readings'
+                Event::Received(Message::TimeRequest(request)) => {
+                    let receive = DWT::cycle_count();
+                    let reply =
+                        TimeReply::new(&request, receive, DWT::cycle_count(), core_clock as u32);
+                    send(&mut link, &mut health, reply);
+                }
+                // Flashing a new image needs a bootloader this firmware
+                // doesn't have yet, the probe is still the only way
+                Event::Received(
+                    Message::UpdateBegin(_) | Message::UpdateChunk(_) | Message::UpdateCommit(_),
+                ) => {
+                    send(
+                        &mut link,
+                        &mut health,
+                        UpdateReply::new(UpdateStatus::Unsupported, 0),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(state) = monitor.update(now) {
+            defmt::debug!(
+                "Link with the Raspberry Pi: {:?}",
+                defmt::Debug2Format(&state)
+            );
+            if state == LinkState::Lost {
+                // Readings kept while nobody listens would be stale by the
+                // time the Raspberry Pi is back
+                batches = [None; 3];
+                summaries = Channel::ALL.map(Accumulator::new);
+            }
+        }
+        // A Raspberry Pi that doesn't send heartbeats may be listening even
+        // if it's quiet
+        let listening =
+            monitor.state() != LinkState::Lost || !usable.contains(MessageKind::Heartbeat);
+
+        if usable.contains(MessageKind::Heartbeat) {
+            if let Some(heartbeat) = heartbeats.poll(now) {
+                send(&mut link, &mut health, heartbeat);
+            }
+        }
+
+        health.uptime = (cycles / core_clock) as u32;
+        if health.uptime >= next_report && usable.contains(MessageKind::DeviceStatus) {
+            let report = health;
+            send(&mut link, &mut health, report);
+            next_report = health.uptime + STATUS_PERIOD;
+        }
+
+        let timestamp = DWT::cycle_count();
+        let (temp, readings) = match sensor.as_mut().map(|sensor| read_sensor(sensor, &mut i2c1)) {
+            Some(Ok(read)) => read,
+            result => {
+                if let Some(Err(e)) = result {
+                    defmt::debug!("Failed to read the sensor: {:?}", defmt::Debug2Format(&e));
+                    health.sensor_errors = health.sensor_errors.saturating_add(1);
+                }
+                // Nothing to send or show, try again later
+                delay.delay_ms(100u32);
+                continue;
+            }
+        };
+
+        if streaming && listening && summary_period > 0 {
+            for reading in readings.iter() {
+                for (channel, value) in Channel::values(reading) {
+                    summaries[channel as usize].push(timestamp, value);
+                }
+            }
+            if now.wrapping_sub(window_start) >= summary_period {
+                window_start = now;
+                for summary in summaries.iter_mut() {
+                    if let Some(stats) = summary.take() {
+                        send(&mut link, &mut health, stats);
+                    }
+                }
+            }
+        } else if streaming && listening {
+            for (i, &reading) in readings.iter().enumerate() {
+                let batch = batches[i].get_or_insert_with(|| {
+                    Batch::new(reading.kind(), timestamp).expect("Readings can be batched")
+                });
+                if let Err(e) = batch.push(reading) {
+                    defmt::debug!("Dropped reading: {:?}", defmt::Debug2Format(&e));
+                }
+
+                if batch.is_full() {
+                    // Readings are taken once per loop, close enough to a
+                    // steady period
+                    batch.period =
+                        timestamp.wrapping_sub(batch.timestamp) / (batch.len() as u32 - 1);
+                    sequencers[i].stamp_batch(batch);
+                    if usable.contains(MessageKind::DeltaBatch) {
+                        send(&mut link, &mut health, batch.compressed());
+                    } else {
+                        send(&mut link, &mut health, *batch);
+                    }
+                    batches[i] = None;
+                }
+            }
+        }
+
+        if sample_requested {
+            sample_requested = false;
+            for (&reading, sequencer) in readings.iter().zip(&mut sequencers) {
+                let sample = sequencer.stamp(timestamp, reading);
+                send(&mut link, &mut health, sample);
+            }
+        }
+
+        defmt::trace!("Temperature: {}", temp);
+
+        // Formatting a float using the whole display, in °C
 
-        if temp < -9.99 {
+        if temp < Celsius(-9.99) {
             ht16k33
                 .update_buffer_with_float(Index::One, -9.99, 2, 10)
                 .unwrap()
-        } else if temp < 0.0 {
+        } else if temp < Celsius(0.0) {
             ht16k33
-                .update_buffer_with_float(Index::One, temp, 2, 10)
+                .update_buffer_with_float(Index::One, temp.0, 2, 10)
                 .unwrap()
-        } else if temp < 10.0 {
+        } else if temp < Celsius(10.0) {
             ht16k33
-                .update_buffer_with_float(Index::Two, temp, 2, 10)
+                .update_buffer_with_float(Index::Two, temp.0, 2, 10)
                 .unwrap();
             ht16k33.update_buffer_with_digit(Index::One, 0)
-        } else if temp < 100.0 {
+        } else if temp < Celsius(100.0) {
             ht16k33
-                .update_buffer_with_float(Index::One, temp, 2, 10)
+                .update_buffer_with_float(Index::One, temp.0, 2, 10)
                 .unwrap()
         } else {
             ht16k33
@@ -95,12 +399,20 @@ fn main() -> ! {
                 .unwrap()
         }
 
+        let mut written = false;
         'retries: for _ in 0..5 {
             match ht16k33.write_display_buffer() {
-                Ok(_) => break 'retries,
+                Ok(_) => {
+                    written = true;
+                    break 'retries;
+                }
                 Err(e) => defmt::debug!("{:?}", e),
             }
             defmt::debug!("Retrying write_display_buffer");
+            health.display_retries = health.display_retries.saturating_add(1);
+        }
+        if !written {
+            health.display_errors = health.display_errors.saturating_add(1);
         }
 
         delay.delay_ms(100u32);
@@ -108,3 +420,108 @@ fn main() -> ! {
 
     nucleo_sensors::exit()
 }
+
+/// Tells why the board last reset from the RCC's reset flags
+fn reset_reason(rcc: &RCC) -> ResetReason {
+    let rsr = rcc.rsr.read();
+    // Flags aren't exclusive: a power on reset also sets the brown-out flag,
+    // and every reset sets the pin flag
+    let reason = if rsr.porrstf().bit_is_set() {
+        ResetReason::PowerOn
+    } else if rsr.borrstf().bit_is_set() {
+        ResetReason::Brownout
+    } else if rsr.iwdg1rstf().bit_is_set() || rsr.wwdg1rstf().bit_is_set() {
+        ResetReason::Watchdog
+    } else if rsr.sftrstf().bit_is_set() {
+        ResetReason::Software
+    } else if rsr.pinrstf().bit_is_set() {
+        ResetReason::Pin
+    } else {
+        ResetReason::Unknown
+    };
+
+    // Clear the flags so the next reset doesn't find them set
+    rcc.rsr.modify(|_, w| w.rmvf().set_bit());
+    reason
+}
+
+fn init_sensor(i2c: &mut I2c<I2C1>) -> Result<Ism330Dhcx, i2c::Error> {
+    let mut sensor = Ism330Dhcx::new(i2c)?;
+    sensor
+        .ctrl1xl
+        .set_accelerometer_data_rate(i2c, Odr_Xl::Hz52)?;
+    sensor.ctrl2g.set_gyroscope_data_rate(i2c, Odr::Hz52)?;
+    Ok(sensor)
+}
+
+/// Reads the temperature and every reading sent to the Raspberry Pi
+fn read_sensor(
+    sensor: &mut Ism330Dhcx,
+    i2c: &mut I2c<I2C1>,
+) -> Result<(Celsius, [SensorMessage; 3]), i2c::Error> {
+    let temp = Celsius(sensor.get_temperature(i2c)?);
+    let [ax, ay, az] = sensor.get_accelerometer(i2c)?;
+    let [gx, gy, gz] = sensor.get_gyroscope(i2c)?;
+
+    // Fixed point temperature spares the Raspberry Pi from using its FPU
+    let readings = [
+        SensorMessage::TemperatureCenti(CentiCelsius::from(Temperature::from(temp))),
+        SensorMessage::Acceleration {
+            x: ax as f32,
+            y: ay as f32,
+            z: az as f32,
+        },
+        SensorMessage::AngularRate {
+            x: gx as f32,
+            y: gy as f32,
+            z: gz as f32,
+        },
+    ];
+    Ok((temp, readings))
+}
+
+fn accel_odr(odr: AccelOdr) -> Odr_Xl {
+    match odr {
+        AccelOdr::Hz12_5 => Odr_Xl::Hz12_5,
+        AccelOdr::Hz26 => Odr_Xl::Hz26,
+        AccelOdr::Hz52 => Odr_Xl::Hz52,
+        AccelOdr::Hz104 => Odr_Xl::Hz104,
+        AccelOdr::Hz208 => Odr_Xl::Hz208,
+        AccelOdr::Hz416 => Odr_Xl::Hz416,
+        AccelOdr::Hz833 => Odr_Xl::Hz833,
+    }
+}
+
+/// Both halves of USART6, carrying the link with the Raspberry Pi
+struct Uart {
+    tx: Tx<USART6>,
+    rx: Rx<USART6>,
+}
+
+impl Transport for Uart {
+    type Error = serial::Error;
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), serial::Error> {
+        for &byte in bytes {
+            block!(self.tx.write(byte))?;
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, serial::Error> {
+        match self.rx.read() {
+            Ok(byte) => Ok(Some(byte)),
+            Err(nb::Error::WouldBlock) => Ok(None),
+            Err(nb::Error::Other(e)) => Err(e),
+        }
+    }
+}
+
+/// Sends any message to the Raspberry Pi as a framed packet, counting it in
+/// `health` if it couldn't be written
+fn send(link: &mut Link<Uart, WINDOW>, health: &mut DeviceStatus, message: impl Into<Message>) {
+    if let Err(e) = link.send(message) {
+        defmt::debug!("Failed to send a message: {:?}", defmt::Debug2Format(&e));
+        health.link_errors = health.link_errors.saturating_add(1);
+    }
+}
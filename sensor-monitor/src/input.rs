@@ -0,0 +1,107 @@
+//! Where the bytes sent over the link are read from
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use common_types::capture::{self, Record};
+
+/// How long a read from a serial port waits before trying again
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Bytes to decode, stamped with when they were read
+pub enum Input {
+    /// Bytes read as they arrive, from a serial port, pty, file or stdin
+    Live {
+        reader: Box<dyn Read>,
+        start: Instant,
+        /// Capture every read is also written to, if any
+        record: Option<capture::Writer<File>>,
+    },
+    /// Capture replayed with the timestamps it was recorded with
+    Capture(capture::Reader<Box<dyn Read>>),
+}
+
+impl Input {
+    /// Opens `path`, `-` being stdin, as a capture if `capture` is set.
+    /// Character devices, like ttys and ptys, are opened as serial ports
+    /// running at `baud`.
+    pub fn open(path: &Path, baud: u32, capture: bool) -> Result<Self, capture::Error> {
+        let reader: Box<dyn Read> = if path == Path::new("-") {
+            Box::new(io::stdin())
+        } else if path.metadata()?.file_type().is_char_device() {
+            let port = serialport::new(path.to_string_lossy(), baud)
+                .timeout(TIMEOUT)
+                .open()
+                .map_err(io::Error::from)?;
+            Box::new(port)
+        } else {
+            Box::new(File::open(path)?)
+        };
+
+        if capture {
+            Ok(Input::Capture(capture::Reader::new(reader)?))
+        } else {
+            Ok(Input::Live {
+                reader,
+                start: Instant::now(),
+                record: None,
+            })
+        }
+    }
+
+    /// Writes every byte read from now on to a capture in `file`, replaying
+    /// a capture isn't recorded again
+    pub fn record(&mut self, file: File) -> Result<(), capture::Error> {
+        if let Input::Live { record, .. } = self {
+            *record = Some(capture::Writer::new(file)?);
+        }
+        Ok(())
+    }
+
+    /// Reads the next bytes, `None` at the end of the input
+    pub fn read(&mut self) -> Result<Option<Record>, capture::Error> {
+        let (reader, start, record) = match self {
+            Input::Live {
+                reader,
+                start,
+                record,
+            } => (reader, start, record),
+            Input::Capture(reader) => return reader.read_record(),
+        };
+
+        let mut buf = [0; 256];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(n) => {
+                    let timestamp = start.elapsed().as_micros() as u64;
+                    if let Some(record) = record {
+                        record.write(timestamp, &buf[..n])?;
+                    }
+                    return Ok(Some(Record {
+                        timestamp,
+                        bytes: buf[..n].to_vec(),
+                    }));
+                }
+                // Serial ports time out while the link is quiet
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+                    ) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Iterator for Input {
+    type Item = Result<Record, capture::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
@@ -0,0 +1,138 @@
+//! Decodes the frames sent over the link and prints them
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use common_types::batch::DeltaBatch;
+use common_types::capture::Messages;
+use common_types::frame::{DecodeError, Decoder};
+use common_types::message::{Message, MessageKind};
+use common_types::sample::{Sample, SequenceStatus, SequenceTracker};
+use common_types::SensorMessage;
+
+mod input;
+mod output;
+
+use input::Input;
+use output::{Event, Format, Output};
+
+/// Decodes the messages sent over the link, from a serial port, a pty, a
+/// file, stdin or a capture, and prints them along with any errors
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Serial port, pty or file to read, `-` for stdin
+    #[arg(default_value = "-")]
+    input: PathBuf,
+    /// Input is a capture written with `--record`
+    #[arg(long)]
+    capture: bool,
+    /// Baud rate of serial ports
+    #[arg(long, default_value_t = 9600)]
+    baud: u32,
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+    /// Also writes the bytes read to a capture
+    #[arg(long, value_name = "FILE", conflicts_with = "capture")]
+    record: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let mut input = Input::open(&args.input, args.baud, args.capture)
+        .map_err(|e| format!("{}: {}", args.input.display(), e))?;
+    if let Some(path) = &args.record {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        input.record(file)?;
+    }
+    let mut monitor = Monitor {
+        output: Output::new(args.format, io::stdout().lock())?,
+        trackers: [
+            SequenceTracker::new(),
+            SequenceTracker::new(),
+            SequenceTracker::new(),
+        ],
+        bad_frames: 0,
+    };
+
+    // Live input and captures are decoded alike
+    for decoded in Messages::new(input, Decoder::new()) {
+        let decoded = decoded?;
+        monitor.decoded(decoded.timestamp, decoded.message)?;
+    }
+
+    let lost: u32 = monitor.trackers.iter().map(SequenceTracker::lost).sum();
+    let duplicated: u32 = monitor
+        .trackers
+        .iter()
+        .map(SequenceTracker::duplicated)
+        .sum();
+    eprintln!(
+        "{} bad frames, {} samples lost, {} duplicated",
+        monitor.bad_frames, lost, duplicated
+    );
+    Ok(())
+}
+
+/// Prints what's decoded and checks the sequence numbers of the samples
+struct Monitor<W: Write> {
+    output: Output<W>,
+    trackers: [SequenceTracker; 3],
+    bad_frames: u32,
+}
+
+impl<W: Write> Monitor<W> {
+    /// Handles a frame decoded `time` microseconds after the input started
+    fn decoded(&mut self, time: u64, decoded: Result<Message, DecodeError>) -> io::Result<()> {
+        let message = match decoded {
+            Ok(message) => message,
+            Err(e) => {
+                self.bad_frames += 1;
+                return self.output.event(time, Event::Error(e));
+            }
+        };
+
+        self.output.event(time, Event::Message(&message))?;
+        for sample in samples(&message) {
+            let kind = sample.value.kind();
+            let status = self.trackers[stream(kind)].update(sample.seq, sample.timestamp);
+            if let SequenceStatus::Gap { .. } | SequenceStatus::Duplicate = status {
+                self.output.event(time, Event::Sequence { kind, status })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Samples a message holds, each numbered by the stream of its kind of
+/// readings
+fn samples(message: &Message) -> Vec<Sample<SensorMessage>> {
+    match message {
+        Message::Sample(sample) => vec![*sample],
+        Message::Batch(batch) | Message::DeltaBatch(DeltaBatch(batch)) => batch.samples().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Index of the tracker for the readings of `kind`
+fn stream(kind: MessageKind) -> usize {
+    match kind {
+        MessageKind::Acceleration => 1,
+        MessageKind::AngularRate => 2,
+        _ => 0,
+    }
+}
@@ -0,0 +1,183 @@
+//! How decoded messages and errors are printed
+
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use common_types::batch::DeltaBatch;
+use common_types::frame::DecodeError;
+use common_types::message::{Message, MessageKind};
+use common_types::sample::{Sample, SequenceStatus};
+use common_types::stats::Channel;
+use common_types::SensorMessage;
+use serde_json::json;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A line per message or error
+    Human,
+    /// A JSON object per line, errors have an `error` field
+    Json,
+    /// A row per value read, anything else is printed to stderr
+    Csv,
+}
+
+/// Something that happened on the link
+#[derive(Clone, Copy, Debug)]
+pub enum Event<'a> {
+    Message(&'a Message),
+    /// Frame that couldn't be decoded
+    Error(DecodeError),
+    /// Sample of the readings of `kind` that didn't follow the previous one
+    Sequence {
+        kind: MessageKind,
+        status: SequenceStatus,
+    },
+}
+
+pub struct Output<W: Write> {
+    format: Format,
+    out: W,
+}
+
+impl<W: Write> Output<W> {
+    /// Starts printing to `out`, with a header if the format has one
+    pub fn new(format: Format, mut out: W) -> io::Result<Self> {
+        if format == Format::Csv {
+            writeln!(out, "time_us,seq,timestamp,channel,value")?;
+        }
+        Ok(Output { format, out })
+    }
+
+    /// Prints `event`, which happened `time` microseconds after the input
+    /// started
+    pub fn event(&mut self, time: u64, event: Event) -> io::Result<()> {
+        match self.format {
+            Format::Human => human(&mut self.out, time, event),
+            Format::Json => writeln!(self.out, "{}", self::json(time, event)),
+            Format::Csv => match event {
+                Event::Message(message) => csv(&mut self.out, time, message),
+                _ => human(&mut io::stderr(), time, event),
+            },
+        }
+    }
+}
+
+fn human(out: &mut impl Write, time: u64, event: Event) -> io::Result<()> {
+    let time = time as f64 / 1e6;
+    match event {
+        Event::Message(Message::Batch(batch))
+        | Event::Message(Message::DeltaBatch(DeltaBatch(batch))) => {
+            for sample in batch.samples() {
+                writeln!(out, "{:>12.6} {}", time, sample_line(&sample))?;
+            }
+            Ok(())
+        }
+        Event::Message(message) => writeln!(out, "{:>12.6} {}", time, message_line(message)),
+        Event::Error(e) => writeln!(out, "{:>12.6} error: {}", time, error_line(e)),
+        Event::Sequence { kind, status } => {
+            let status = match status {
+                SequenceStatus::Gap { lost } => format!("{} lost", lost),
+                SequenceStatus::Duplicate => "duplicated".into(),
+                SequenceStatus::First | SequenceStatus::InOrder => "in order".into(),
+            };
+            writeln!(out, "{:>12.6} error: {:?} samples {}", time, kind, status)
+        }
+    }
+}
+
+fn message_line(message: &Message) -> String {
+    match message {
+        Message::Sensor(reading) => reading_line(reading),
+        Message::Sample(sample) => sample_line(sample),
+        Message::Stats(stats) => format!(
+            "{:?} over {} readings from @{}: min {} max {} mean {} variance {}",
+            stats.channel,
+            stats.count,
+            stats.timestamp,
+            stats.min,
+            stats.max,
+            stats.mean,
+            stats.variance
+        ),
+        Message::Sequenced(sequenced) => match sequenced.message() {
+            Ok(message) => format!("#{} {}", sequenced.seq, message_line(&message)),
+            Err(e) => format!("#{} {:?}", sequenced.seq, e),
+        },
+        message => format!("{:?}", message),
+    }
+}
+
+fn sample_line(sample: &Sample<SensorMessage>) -> String {
+    format!(
+        "#{} @{} {}",
+        sample.seq,
+        sample.timestamp,
+        reading_line(&sample.value)
+    )
+}
+
+fn reading_line(reading: &SensorMessage) -> String {
+    match *reading {
+        SensorMessage::Temperature(temp) => format!("temperature {:.2}", temp.celsius()),
+        // Printed as is, going through a float could round it differently
+        SensorMessage::TemperatureCenti(temp) => format!(
+            "temperature {}{}.{:02} °C",
+            if temp.0 < 0 { "-" } else { "" },
+            temp.degrees().abs(),
+            temp.hundredths().abs()
+        ),
+        SensorMessage::Acceleration { x, y, z } => {
+            format!("acceleration {:.3} {:.3} {:.3} m/s²", x, y, z)
+        }
+        SensorMessage::AngularRate { x, y, z } => {
+            format!("angular rate {:.3} {:.3} {:.3} °/s", x, y, z)
+        }
+    }
+}
+
+fn error_line(e: DecodeError) -> String {
+    match e {
+        DecodeError::BadCrc => "bad CRC".into(),
+        DecodeError::BadTag => "bad tag".into(),
+        DecodeError::UnknownKind(kind) => format!("unknown kind 0x{:02X}", kind),
+        DecodeError::Oversize(len) => format!("payload of {} bytes is too long", len),
+        DecodeError::InvalidLength => "invalid length".into(),
+        DecodeError::Invalid(e) => format!("invalid payload, {:?}", e),
+    }
+}
+
+fn json(time: u64, event: Event) -> serde_json::Value {
+    match event {
+        Event::Message(message) => json!({ "time_us": time, "message": message }),
+        Event::Error(e) => json!({ "time_us": time, "error": format!("{:?}", e) }),
+        Event::Sequence { kind, status } => match status {
+            SequenceStatus::Gap { lost } => {
+                json!({ "time_us": time, "error": "Gap", "kind": kind, "lost": lost })
+            }
+            _ => json!({ "time_us": time, "error": format!("{:?}", status), "kind": kind }),
+        },
+    }
+}
+
+/// Writes a row per value in the readings `message` holds, if any
+fn csv(out: &mut impl Write, time: u64, message: &Message) -> io::Result<()> {
+    let mut row = |seq: Option<u16>, timestamp: Option<u32>, reading: &SensorMessage| {
+        let seq = seq.map(|seq| seq.to_string()).unwrap_or_default();
+        let timestamp = timestamp.map(|ts| ts.to_string()).unwrap_or_default();
+        Channel::values(reading).try_for_each(|(channel, value)| {
+            writeln!(
+                out,
+                "{},{},{},{:?},{}",
+                time, seq, timestamp, channel, value
+            )
+        })
+    };
+    match message {
+        Message::Sensor(reading) => row(None, None, reading),
+        Message::Sample(sample) => row(Some(sample.seq), Some(sample.timestamp), &sample.value),
+        Message::Batch(batch) | Message::DeltaBatch(DeltaBatch(batch)) => batch
+            .samples()
+            .try_for_each(|s| row(Some(s.seq), Some(s.timestamp), &s.value)),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use common_types::capture::Writer;
+use common_types::frame::MAX_FRAME_SIZE;
+use common_types::message::Message;
+use common_types::{CentiCelsius, Sample, SensorMessage};
+
+fn frame(message: impl Into<Message>) -> Vec<u8> {
+    let mut buf = [0; MAX_FRAME_SIZE];
+    let len = message.into().to_frame(&mut buf).unwrap();
+    buf[..len].to_vec()
+}
+
+fn sample(seq: u16) -> Sample<SensorMessage> {
+    Sample {
+        seq,
+        timestamp: 100 * seq as u32,
+        value: SensorMessage::Acceleration {
+            x: 0.5,
+            y: 0.0,
+            z: 9.75,
+        },
+    }
+}
+
+fn monitor(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sensor-monitor"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    output
+}
+
+#[test]
+fn replays_a_capture_as_json() {
+    let mut corrupted = frame(sample(1));
+    corrupted[6] ^= 0xFF;
+
+    let mut writer = Writer::new(Vec::new()).unwrap();
+    writer.write(1_000, &frame(sample(0))).unwrap();
+    writer.write(2_000, &corrupted).unwrap();
+    writer.write(3_000, &frame(sample(3))).unwrap();
+    let capture = writer.into_inner();
+
+    let output = monitor(&["--capture", "--format", "json"], &capture);
+    let lines: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 4, "{:?}", lines);
+    assert_eq!(lines[0]["time_us"], 1_000);
+    assert_eq!(lines[0]["message"]["Sample"]["seq"], 0);
+    assert_eq!(
+        lines[1],
+        serde_json::json!({ "time_us": 2_000, "error": "BadCrc" })
+    );
+    assert_eq!(lines[2]["message"]["Sample"]["seq"], 3);
+    assert_eq!(
+        lines[3],
+        serde_json::json!({ "time_us": 3_000, "error": "Gap", "kind": "Acceleration", "lost": 2 })
+    );
+
+    let summary = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(summary, "1 bad frames, 2 samples lost, 0 duplicated\n");
+}
+
+#[test]
+fn prints_readings_from_stdin_as_csv() {
+    let stream = [
+        frame(sample(7)),
+        frame(SensorMessage::TemperatureCenti(CentiCelsius(2150))),
+    ]
+    .concat();
+
+    let output = monitor(&["--format", "csv"], &stream);
+    let rows: Vec<Vec<String>> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split(',').map(String::from).collect())
+        .collect();
+    let columns: Vec<&[String]> = rows.iter().map(|row| &row[1..]).collect();
+    assert_eq!(
+        columns,
+        [
+            &["seq", "timestamp", "channel", "value"][..],
+            &["7", "700", "AccelerationX", "0.5"],
+            &["7", "700", "AccelerationY", "0"],
+            &["7", "700", "AccelerationZ", "9.75"],
+            &["", "", "Temperature", "21.5"],
+        ]
+    );
+}
+
+#[test]
+fn prints_frames_hidden_at_the_end_of_the_input() {
+    // A header announcing more bytes than the input has left, then a sample
+    let stream = [&[0xAA, 0x55, 0x01, 100][..], &frame(sample(2))].concat();
+
+    let output = monitor(&[], &stream);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.ends_with(" #2 @200 acceleration 0.500 0.000 9.750 m/s²\n"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn prints_hundredths_of_a_degree_exactly() {
+    let stream = [
+        frame(SensorMessage::TemperatureCenti(CentiCelsius(-5))),
+        frame(SensorMessage::TemperatureCenti(CentiCelsius(-1234))),
+        frame(SensorMessage::TemperatureCenti(CentiCelsius(8500))),
+    ]
+    .concat();
+
+    let output = monitor(&[], &stream);
+    let readings: Vec<&str> = std::str::from_utf8(&output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split_once(" temperature").unwrap().1)
+        .collect();
+    assert_eq!(readings, [" -0.05 °C", " -12.34 °C", " 85.00 °C"]);
+}